@@ -0,0 +1,101 @@
+//! Compares the interpreters of the Intcode computer on puzzle inputs.
+//!
+//! Run with `cargo bench --bench intcode`.
+use aoc19::intcode_computer::fast::FastComputer;
+use aoc19::intcode_computer::ir::IrComputer;
+use aoc19::intcode_computer::{parse_input, Computer, RunState};
+use criterion::{criterion_group, criterion_main, Criterion};
+
+fn input(day: u32) -> Vec<i64> {
+    let path = format!("input/2019/day{}.txt", day);
+    let source = std::fs::read_to_string(path).expect("Failed to read the input");
+    parse_input(source.trim())
+}
+
+/// Day 7 part two : amplifiers in a feedback loop, with phases 9,8,7,6,5
+fn day7_computer(code: &[i64]) -> i64 {
+    let mut amps: Vec<Computer> = (5..10)
+        .rev()
+        .map(|phase| {
+            let mut amp = Computer::new(code.to_vec()).halt_on_output();
+            amp.input(phase);
+            amp
+        })
+        .collect();
+    let mut signal = 0;
+    for idx in (0..5).cycle() {
+        amps[idx].input(signal);
+        match amps[idx].execute().unwrap() {
+            RunState::ProducedOutput(o) => signal = o,
+            _ => return signal,
+        }
+    }
+    unreachable!()
+}
+
+fn day7_fast(code: &[i64]) -> i64 {
+    let mut amps: Vec<FastComputer> = (5..10)
+        .rev()
+        .map(|phase| {
+            let mut amp = FastComputer::new(code.to_vec()).halt_on_output();
+            amp.input(phase);
+            amp
+        })
+        .collect();
+    let mut signal = 0;
+    for idx in (0..5).cycle() {
+        amps[idx].input(signal);
+        match amps[idx].execute().unwrap() {
+            RunState::ProducedOutput(o) => signal = o,
+            _ => return signal,
+        }
+    }
+    unreachable!()
+}
+
+fn intcode(c: &mut Criterion) {
+    let day7 = input(7);
+    let mut group = c.benchmark_group("day7");
+    group.bench_function("computer", |b| b.iter(|| day7_computer(&day7)));
+    group.bench_function("fast", |b| b.iter(|| day7_fast(&day7)));
+    group.finish();
+
+    // Day 9 part two : the sensor boost, and day 13 part one : the whole game
+    for (day, input_value) in [(9, Some(2)), (13, None)].iter() {
+        let code = input(*day);
+        let mut group = c.benchmark_group(format!("day{}", day));
+        group.sample_size(10);
+        group.bench_function("computer", |b| {
+            b.iter(|| {
+                let mut computer = Computer::new(code.clone());
+                if let Some(value) = input_value {
+                    computer.input(*value);
+                }
+                computer.execute().unwrap()
+            })
+        });
+        group.bench_function("fast", |b| {
+            b.iter(|| {
+                let mut computer = FastComputer::new(code.clone());
+                if let Some(value) = input_value {
+                    computer.input(*value);
+                }
+                computer.execute().unwrap()
+            })
+        });
+        // Day 13 writes over its code, the plain interpreter taking over
+        group.bench_function("ir", |b| {
+            b.iter(|| {
+                let mut computer = IrComputer::new(code.clone());
+                if let Some(value) = input_value {
+                    computer.input(*value);
+                }
+                computer.execute().unwrap()
+            })
+        });
+        group.finish();
+    }
+}
+
+criterion_group!(benches, intcode);
+criterion_main!(benches);
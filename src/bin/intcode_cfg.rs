@@ -0,0 +1,43 @@
+//! Static analysis of Intcode programs.
+//!
+//! Usage: `intcode_cfg <program>` prints a summary of the control flow graph,
+//! `intcode_cfg --dot <program>` prints the graph in the Graphviz DOT format.
+use aoc19::intcode_computer::cfg::Cfg;
+use aoc19::intcode_computer::parse_input;
+
+fn main() {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let (dot, path) = match args.as_slice() {
+        [flag, path] if flag == "--dot" => (true, path),
+        [path] => (false, path),
+        _ => {
+            eprintln!("usage: intcode_cfg [--dot] <program>");
+            std::process::exit(1);
+        }
+    };
+    let source = std::fs::read_to_string(path).expect("Failed to read the program");
+    let cfg = Cfg::build(&parse_input(source.trim()));
+    if dot {
+        print!("{}", cfg.to_dot());
+        return;
+    }
+
+    let blocks: Vec<_> = cfg.blocks().collect();
+    println!("blocks:          {}", blocks.len());
+    println!("edges:           {}", cfg.edges().len());
+    let computed: Vec<usize> = blocks
+        .iter()
+        .filter(|b| b.computed)
+        .map(|b| b.start())
+        .collect();
+    println!("computed jumps:  {:?}", computed);
+    for modification in cfg.self_modifications() {
+        println!(
+            "self-modifying:  {} writes [{}]",
+            modification.instruction, modification.target
+        );
+    }
+    for range in cfg.unreachable() {
+        println!("unreachable:     {}..{}", range.start, range.end);
+    }
+}
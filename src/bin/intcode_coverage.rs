@@ -0,0 +1,47 @@
+//! Code coverage of Intcode programs.
+//!
+//! Usage: `intcode_coverage [--lcov] <program> [input...]` runs the program once per input,
+//! each input being a comma separated list of values, and prints the disassembly annotated
+//! with the coverage of all the runs. With `--lcov`, prints an lcov tracefile instead.
+use aoc19::intcode_computer::coverage::Coverage;
+use aoc19::intcode_computer::{parse_input, Computer};
+
+fn main() {
+    let mut args: Vec<String> = std::env::args().skip(1).collect();
+    let lcov = args.first().map(String::as_str) == Some("--lcov");
+    if lcov {
+        args.remove(0);
+    }
+    let (path, inputs) = match args.split_first() {
+        Some((path, inputs)) => (path, inputs),
+        None => {
+            eprintln!("usage: intcode_coverage [--lcov] <program> [input...]");
+            std::process::exit(1);
+        }
+    };
+    let source = std::fs::read_to_string(path).expect("Failed to read the program");
+    let code = parse_input(source.trim());
+
+    let runs: Vec<Vec<i64>> = match inputs {
+        [] => vec![vec![]],
+        _ => inputs.iter().map(|input| parse_input(input)).collect(),
+    };
+    let mut coverage = Coverage::default();
+    for input in runs {
+        let mut computer = Computer::new(code.clone()).record_coverage();
+        for value in input.iter() {
+            computer.input(*value);
+        }
+        match computer.execute() {
+            Ok(state) => eprintln!("input {:?}: {:?}", input, state),
+            Err(e) => eprintln!("input {:?}: {}", input, e),
+        }
+        coverage.merge(computer.coverage().unwrap());
+    }
+
+    if lcov {
+        print!("{}", coverage.to_lcov(&code, path));
+    } else {
+        print!("{}", coverage.annotate(&code));
+    }
+}
@@ -0,0 +1,197 @@
+//! Interactive debugger for Intcode programs.
+//!
+//! Usage: `intcode_debugger <program>`, then type `help` for the list of commands.
+use aoc19::intcode_computer::debugger::{Debugger, Stop};
+use aoc19::intcode_computer::{parse_input, Computer};
+use std::io::{self, BufRead, Write};
+
+const HELP: &str = "\
+s, step [n]          executes n instructions (default 1)
+c, continue          executes until a breakpoint, a watchpoint or a stop
+b, break <addr>      sets a breakpoint
+db <addr>            deletes a breakpoint
+w, watch <addr>      watches a memory cell
+dw <addr>            deletes a watchpoint
+i, info              shows the pointer, relative base, queues, breakpoints and watchpoints
+x <addr> [n]         shows n memory cells (default 1)
+set <addr> <value>   writes a memory cell
+in <values...>       adds values to the input queue
+out                  shows and clears the output queue
+l, list [addr] [n]   decodes n instructions from addr (default: pointer, 5)
+q, quit              exits";
+
+fn print_stop(stop: &Stop) {
+    match stop {
+        Stop::Breakpoint(addr) => println!("breakpoint at {}", addr),
+        Stop::Watchpoint { address, old, new } => {
+            println!("watchpoint: [{}] changed from {} to {}", address, old, new)
+        }
+        Stop::Computer(state) => println!("computer stopped: {:?}", state),
+    }
+}
+
+fn print_current(debugger: &Debugger) {
+    let pointer = debugger.computer().pointer();
+    match debugger.current_instruction() {
+        Ok(instr) => println!("{:>6}  {}", pointer, instr),
+        Err(e) => println!("{:>6}  <{}>", pointer, e),
+    }
+}
+
+fn list(computer: &Computer, from: usize, count: usize) {
+    let mut addr = from;
+    for _ in 0..count {
+        match computer.instruction_at(addr) {
+            Ok(instr) => {
+                println!("{:>6}  {}", addr, instr);
+                addr += instr.args_count() + 1;
+            }
+            Err(e) => {
+                println!("{:>6}  <{}>", addr, e);
+                break;
+            }
+        }
+    }
+}
+
+/// Executes a single command. Returns false when the debugger should exit
+fn command(debugger: &mut Debugger, words: &[&str]) -> Result<bool, String> {
+    let arg = |idx: usize| -> Result<i64, String> {
+        words
+            .get(idx)
+            .ok_or_else(|| format!("missing argument {}", idx))?
+            .parse::<i64>()
+            .map_err(|e| e.to_string())
+    };
+    let addr = |idx: usize| -> Result<usize, String> {
+        let value = arg(idx)?;
+        if value < 0 {
+            return Err(format!("invalid address {}", value));
+        }
+        Ok(value as usize)
+    };
+
+    match words[0] {
+        "s" | "step" => {
+            let count = if words.len() > 1 { arg(1)? } else { 1 };
+            for _ in 0..count {
+                print_current(debugger);
+                if let Some(stop) = debugger.step().map_err(|e| e.to_string())? {
+                    print_stop(&stop);
+                    break;
+                }
+            }
+            print_current(debugger);
+        }
+        "c" | "continue" => {
+            let stop = debugger.resume().map_err(|e| e.to_string())?;
+            print_stop(&stop);
+            print_current(debugger);
+        }
+        "b" | "break" => {
+            debugger.add_breakpoint(addr(1)?);
+        }
+        "db" => {
+            if !debugger.remove_breakpoint(addr(1)?) {
+                return Err("no such breakpoint".to_string());
+            }
+        }
+        "w" | "watch" => {
+            debugger.add_watchpoint(addr(1)?);
+        }
+        "dw" => {
+            if !debugger.remove_watchpoint(addr(1)?) {
+                return Err("no such watchpoint".to_string());
+            }
+        }
+        "i" | "info" => {
+            let computer = debugger.computer();
+            println!("pointer:       {}", computer.pointer());
+            println!("relative base: {}", computer.relative_base());
+            println!("halted:        {}", computer.halted());
+            println!(
+                "input:         {:?}",
+                computer.pending_input().collect::<Vec<_>>()
+            );
+            println!(
+                "output:        {:?}",
+                computer.get_all_output().collect::<Vec<_>>()
+            );
+            println!(
+                "breakpoints:   {:?}",
+                debugger.breakpoints().collect::<Vec<_>>()
+            );
+            println!(
+                "watchpoints:   {:?}",
+                debugger.watchpoints().collect::<Vec<_>>()
+            );
+        }
+        "x" => {
+            let from = addr(1)?;
+            let count = if words.len() > 2 { addr(2)? } else { 1 };
+            for a in from..from.saturating_add(count) {
+                println!("{:>6}  {}", a, debugger.computer().get(a));
+            }
+        }
+        "set" => {
+            let (a, value) = (addr(1)?, arg(2)?);
+            debugger.computer_mut().set(a, value);
+        }
+        "in" => {
+            for idx in 1..words.len() {
+                let value = arg(idx)?;
+                debugger.computer_mut().input(value);
+            }
+        }
+        "out" => {
+            let computer = debugger.computer_mut();
+            println!("{:?}", computer.get_all_output().collect::<Vec<_>>());
+            computer.clear_output();
+        }
+        "l" | "list" => {
+            let from = if words.len() > 1 {
+                addr(1)?
+            } else {
+                debugger.computer().pointer()
+            };
+            let count = if words.len() > 2 { addr(2)? } else { 5 };
+            list(debugger.computer(), from, count);
+        }
+        "h" | "help" => println!("{}", HELP),
+        "q" | "quit" => return Ok(false),
+        other => return Err(format!("unknown command {}, try help", other)),
+    }
+    Ok(true)
+}
+
+fn main() {
+    let path = match std::env::args().nth(1) {
+        Some(path) => path,
+        None => {
+            eprintln!("usage: intcode_debugger <program>");
+            std::process::exit(1);
+        }
+    };
+    let source = std::fs::read_to_string(&path).expect("Failed to read the program");
+    let mut debugger = Debugger::new(Computer::new(parse_input(source.trim())));
+    print_current(&debugger);
+
+    let stdin = io::stdin();
+    loop {
+        print!("(icdb) ");
+        io::stdout().flush().unwrap();
+        let mut line = String::new();
+        if stdin.lock().read_line(&mut line).unwrap() == 0 {
+            break;
+        }
+        let words: Vec<&str> = line.split_whitespace().collect();
+        if words.is_empty() {
+            continue;
+        }
+        match command(&mut debugger, &words) {
+            Ok(true) => (),
+            Ok(false) => break,
+            Err(e) => println!("error: {}", e),
+        }
+    }
+}
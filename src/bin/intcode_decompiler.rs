@@ -0,0 +1,38 @@
+//! Decompiler of Intcode programs into pseudo-C.
+//!
+//! Usage: `intcode_decompiler [--constants] <program> [addr=name...]`. With `--constants`,
+//! the cells the program never writes are read as constants, except the named ones.
+use aoc19::intcode_computer::decompiler::Decompiler;
+use aoc19::intcode_computer::parse_input;
+
+fn usage() -> ! {
+    eprintln!("usage: intcode_decompiler [--constants] <program> [addr=name...]");
+    std::process::exit(1);
+}
+
+fn main() {
+    let mut args: Vec<String> = std::env::args().skip(1).collect();
+    let constants = args.first().map(|arg| arg == "--constants") == Some(true);
+    if constants {
+        args.remove(0);
+    }
+    if args.is_empty() {
+        usage();
+    }
+    let source = std::fs::read_to_string(&args[0]).expect("Failed to read the program");
+    let code = parse_input(source.trim());
+    let mut decompiler = Decompiler::new(&code);
+    for arg in args[1..].iter() {
+        match arg
+            .split_once('=')
+            .map(|(addr, name)| (addr.parse::<usize>(), name))
+        {
+            Some((Ok(addr), name)) => decompiler = decompiler.name(addr, name),
+            _ => usage(),
+        }
+    }
+    if constants {
+        decompiler = decompiler.constant_memory();
+    }
+    print!("{}", decompiler.decompile());
+}
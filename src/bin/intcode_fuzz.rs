@@ -0,0 +1,44 @@
+//! Fuzzer of the Intcode computer.
+//!
+//! Usage: `intcode_fuzz [--day2] [seed] [runs]` generates random programs and reports the
+//! ones making the computer panic, hang or misreport its state. With `--day2`, the programs
+//! only use the day 2 instructions, and are compared with a standalone day 2 interpreter.
+use aoc19::intcode_computer::fuzz::{fuzz, Dialect};
+
+fn main() {
+    let mut args: Vec<String> = std::env::args().skip(1).collect();
+    let dialect = match args.first().map(String::as_str) {
+        Some("--day2") => {
+            args.remove(0);
+            Dialect::Day2
+        }
+        _ => Dialect::Full,
+    };
+    let numbers: Result<Vec<u64>, _> = args.iter().map(|arg| arg.parse()).collect();
+    let (seed, runs) = match numbers.as_deref() {
+        Ok([]) => (2019, 10_000),
+        Ok([seed]) => (*seed, 10_000),
+        Ok([seed, runs]) => (*seed, *runs),
+        _ => {
+            eprintln!("usage: intcode_fuzz [--day2] [seed] [runs]");
+            std::process::exit(1);
+        }
+    };
+
+    let failures = fuzz(seed, runs as usize, dialect);
+    for failure in failures.iter() {
+        println!("seed {}:", failure.seed);
+        for finding in failure.findings.iter() {
+            println!("    {}", finding);
+        }
+        let code: Vec<String> = failure.case.code.iter().map(|v| v.to_string()).collect();
+        println!("    program: {}", code.join(","));
+        if !failure.case.inputs.is_empty() {
+            println!("    input:   {:?}", failure.case.inputs);
+        }
+    }
+    println!("{} failing programs out of {}", failures.len(), runs);
+    if !failures.is_empty() {
+        std::process::exit(2);
+    }
+}
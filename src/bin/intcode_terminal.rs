@@ -0,0 +1,162 @@
+//! Runs a text driven Intcode program interactively.
+//!
+//! Usage: `intcode_terminal <program> [macro...]`. The macros are replayed before reading
+//! commands from stdin. Lines starting with `!` are commands of the terminal itself,
+//! type `!help` for their list.
+use aoc19::intcode_computer::ascii::is_ascii;
+use aoc19::intcode_computer::snapshot::Snapshot;
+use aoc19::intcode_computer::{parse_input, Computer, RunState};
+use std::io::{self, BufRead, Write};
+
+const HELP: &str = "\
+!save <file>     saves the state of the program
+!load <file>     restores a state saved with !save
+!macro <file>    replays the commands of a file, one per line
+!history         shows the commands sent so far
+!write <file>    writes the commands sent so far to a file, to replay it with !macro
+!quit            exits";
+
+/// Newline to print before a line of the terminal, unless the text already ends one
+fn newline(text: &str) -> &'static str {
+    if text.is_empty() || text.ends_with('\n') {
+        ""
+    } else {
+        "\n"
+    }
+}
+
+struct Terminal {
+    computer: Computer,
+    /// Commands sent to the program, in order
+    history: Vec<String>,
+}
+
+impl Terminal {
+    /// Runs the program until it needs input, printing its output.
+    /// Non-ASCII values, such as answers, are printed on their own line
+    fn run(&mut self) -> Result<RunState, String> {
+        let state = self.computer.execute().map_err(|e| e.to_string())?;
+        let mut stdout = io::stdout();
+        let mut text = String::new();
+        for value in self.computer.output.drain(..) {
+            if is_ascii(value) {
+                text.push(value as u8 as char);
+            } else {
+                text.push_str(&format!("{}[{}]\n", newline(&text), value));
+            }
+        }
+        if state == RunState::Halted {
+            text.push_str(&format!("{}[halted]\n", newline(&text)));
+        }
+        write!(stdout, "{}", text).unwrap();
+        stdout.flush().unwrap();
+        Ok(state)
+    }
+
+    /// Sends a line to the program and runs it
+    fn send(&mut self, line: &str) -> Result<RunState, String> {
+        if self.computer.halted() {
+            return Err("the program is halted".to_string());
+        }
+        self.computer.input_line(line);
+        self.history.push(line.to_string());
+        self.run()
+    }
+
+    /// Replays a macro file, stopping early if the program halts
+    fn replay(&mut self, path: &str) -> Result<(), String> {
+        let script = std::fs::read_to_string(path).map_err(|e| e.to_string())?;
+        for line in script.lines() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            println!("{}", line);
+            if self.send(line)? == RunState::Halted {
+                break;
+            }
+        }
+        Ok(())
+    }
+
+    /// Executes a command of the terminal. Returns false when the terminal should exit
+    fn command(&mut self, words: &[&str]) -> Result<bool, String> {
+        let path = || -> Result<&str, String> {
+            words
+                .get(1)
+                .cloned()
+                .ok_or_else(|| "missing file name".to_string())
+        };
+        match words[0] {
+            "!save" => self
+                .computer
+                .snapshot()
+                .save(path()?)
+                .map_err(|e| e.to_string())?,
+            "!load" => {
+                let snapshot = Snapshot::load(path()?).map_err(|e| e.to_string())?;
+                self.computer = Computer::restore(snapshot).map_err(|e| e.to_string())?;
+                self.run()?;
+            }
+            "!macro" => self.replay(path()?)?,
+            "!history" => {
+                for line in self.history.iter() {
+                    println!("{}", line);
+                }
+            }
+            "!write" => {
+                let mut script = self.history.join("\n");
+                script.push('\n');
+                std::fs::write(path()?, script).map_err(|e| e.to_string())?;
+            }
+            "!help" => println!("{}", HELP),
+            "!quit" => return Ok(false),
+            other => return Err(format!("unknown command {}, try !help", other)),
+        }
+        Ok(true)
+    }
+}
+
+fn main() {
+    let mut args = std::env::args().skip(1);
+    let path = match args.next() {
+        Some(path) => path,
+        None => {
+            eprintln!("usage: intcode_terminal <program> [macro...]");
+            std::process::exit(1);
+        }
+    };
+    let source = std::fs::read_to_string(&path).expect("Failed to read the program");
+    let mut terminal = Terminal {
+        computer: Computer::new(parse_input(source.trim())),
+        history: vec![],
+    };
+    if let Err(e) = terminal.run() {
+        eprintln!("error: {}", e);
+    }
+    for path in args {
+        if let Err(e) = terminal.replay(&path) {
+            eprintln!("error: {}", e);
+        }
+    }
+
+    let stdin = io::stdin();
+    loop {
+        let mut line = String::new();
+        if stdin.lock().read_line(&mut line).unwrap() == 0 {
+            break;
+        }
+        let line = line.trim_end_matches(['\r', '\n']);
+        let result = if line.starts_with('!') {
+            let words: Vec<&str> = line.split_whitespace().collect();
+            terminal.command(&words)
+        } else {
+            terminal.send(line).map(|_| true)
+        };
+        match result {
+            Ok(true) => (),
+            Ok(false) => break,
+            Err(e) => eprintln!("error: {}", e),
+        }
+    }
+}
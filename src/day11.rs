@@ -1,4 +1,4 @@
-use crate::intcode_computer::{parse_input, Computer};
+use crate::intcode_computer::{parse_input, Computer, RunState};
 use std::collections::{HashMap, HashSet};
 
 #[aoc_generator(day11)]
@@ -21,13 +21,13 @@ pub struct PaintingRobot {
 impl PaintingRobot {
     pub fn execute(&mut self, map: &mut HashMap<(i32, i32), i64>) {
         loop {
-            self.computer.execute();
+            let state = self.computer.execute().unwrap();
             let paint_to = self.computer.get_next_output().unwrap();
             let dir = self.computer.get_next_output().unwrap();
 
             self.step(map, paint_to, dir);
 
-            if self.computer.halted() {
+            if state == RunState::Halted {
                 break;
             }
 
@@ -70,9 +70,7 @@ impl PaintingRobot {
 fn part_one(input: &[i64]) -> usize {
     let mut map: HashMap<(i32, i32), i64> = Default::default();
     let mut robot = PaintingRobot {
-        computer: Computer::new(input.to_vec())
-            .halt_on_missing_input()
-            .set_available_memory(1500),
+        computer: Computer::new(input.to_vec()),
         direction: 0,
         position: (0, 0),
         painted: Default::default(),
@@ -87,9 +85,7 @@ fn part_two(input: &[i64]) -> String {
     let mut map: HashMap<(i32, i32), i64> = Default::default();
     map.insert((0, 0), 1);
     let mut robot = PaintingRobot {
-        computer: Computer::new(input.to_vec())
-            .halt_on_missing_input()
-            .set_available_memory(1500),
+        computer: Computer::new(input.to_vec()),
         direction: 0,
         position: (0, 0),
         painted: Default::default(),
@@ -1,4 +1,4 @@
-use crate::intcode_computer::{parse_input, Computer};
+use crate::intcode_computer::{parse_input, Computer, RunState};
 
 #[aoc_generator(day13)]
 fn input_generator(input: &str) -> Vec<i64> {
@@ -7,8 +7,8 @@ fn input_generator(input: &str) -> Vec<i64> {
 
 #[aoc(day13, part1)]
 fn part_one(input: &[i64]) -> usize {
-    let mut computer = Computer::new(input.to_vec()).set_available_memory(3000);
-    computer.execute();
+    let mut computer = Computer::new(input.to_vec());
+    computer.execute().unwrap();
 
     let mut idx = 0;
     computer
@@ -22,61 +22,44 @@ fn part_one(input: &[i64]) -> usize {
         .count()
 }
 
+#[derive(Default)]
 struct GameInfo {
     pub score: i64,
     pub ball: (i64, i64),
     pub paddle: (i64, i64),
-    pub game_finished: bool,
 }
 
 impl GameInfo {
-    fn from_frame(input: Vec<i64>) -> Self {
-        let mut ball = (0, 0);
-        let mut paddle = (0, 0);
-        let mut score = 0;
-        let mut game_finished = true;
-        input
-            .chunks(3)
-            .inspect(|i| println!("{}", i[2]))
-            .for_each(|i| match i[2] {
-                0 | 1 => (),
-                2 => {
-                    game_finished = false;
-                }
-                3 => {
-                    paddle = (i[0], i[1]);
-                }
-                4 => {
-                    ball = (i[0], i[1]);
-                }
-                x => {
-                    score = x;
-                }
-            });
-
-        GameInfo {
-            score,
-            ball,
-            paddle,
-            game_finished,
-        }
+    /// Updates the game state with the tiles drawn during the last frame
+    fn update(&mut self, frame: Vec<i64>) {
+        frame.chunks(3).for_each(|i| match (i[0], i[2]) {
+            (-1, score) => {
+                self.score = score;
+            }
+            (_, 3) => {
+                self.paddle = (i[0], i[1]);
+            }
+            (_, 4) => {
+                self.ball = (i[0], i[1]);
+            }
+            _ => (),
+        });
     }
 }
 
 #[aoc(day13, part2)]
 fn part_two(input: &[i64]) -> i64 {
-    let mut computer = Computer::new(input.to_vec())
-        .set_available_memory(3000)
-        .halt_on_missing_input();
+    let mut computer = Computer::new(input.to_vec());
     computer.set(0, 2);
+    let mut game_info = GameInfo::default();
 
     loop {
-        computer.execute();
-        let frame: Vec<i64> = computer.output.iter().map(|x| *x).collect();
-        let game_info = GameInfo::from_frame(frame);
+        let state = computer.execute().unwrap();
+        let frame: Vec<i64> = computer.output.iter().copied().collect();
+        game_info.update(frame);
         computer.clear_output();
 
-        if game_info.game_finished {
+        if state == RunState::Halted {
             return game_info.score;
         }
 
@@ -88,6 +71,5 @@ fn part_two(input: &[i64]) -> i64 {
             0
         };
         computer.input(input);
-        println!("-----");
     }
 }
@@ -1,5 +1,5 @@
 use crate::intcode_computer::{parse_input, Computer};
-use std::collections::HashSet;
+use std::collections::{HashSet, VecDeque};
 
 #[aoc_generator(day15)]
 fn input_generator(input: &str) -> Vec<i64> {
@@ -9,7 +9,6 @@ fn input_generator(input: &str) -> Vec<i64> {
 type Node = (i32, i32);
 struct Explorer {
     computer: Computer,
-    pos: Node,
     map: HashSet<Node>,
     end: Option<Node>,
 }
@@ -24,54 +23,43 @@ fn neighbour_node(node: &Node, direction: i64) -> Node {
     }
 }
 
-fn inv(dir: i64) -> i64 {
-    match dir {
-        1 => 2,
-        2 => 1,
-        3 => 4,
-        4 => 3,
-        _ => unreachable!(),
-    }
-}
-
 impl Explorer {
     pub fn new(input: &[i64]) -> Self {
         Explorer {
-            computer: Computer::new(input.to_vec()).halt_on_missing_input(),
-            pos: (0, 0),
+            computer: Computer::new(input.to_vec()),
             map: Default::default(),
             end: None,
         }
     }
 
+    /// Explores the whole maze breadth-first.
+    /// Instead of walking back, the droid is forked at each open cell.
     pub fn explore_all(&mut self) {
-        // When we add a new point to the map, we add the invert to the backtrace.
-        let mut back = vec![];
-        while let Some(dir) = (1..5)
-            .filter(|dir| !self.map.contains(&neighbour_node(&self.pos, *dir)))
-            .next()
-            .or_else(|| back.pop())
-        {
-            let new_node = neighbour_node(&self.pos, dir);
-            let new = self.map.insert(new_node);
-            if new {
-                back.push(inv(dir));
-            }
-            // Try to move
-            self.computer.input(dir);
-            self.computer.execute();
-            let out = self.computer.get_next_output().unwrap();
-            if out == 2 {
-                self.end = Some(new_node);
-                break;
-            }
-            if out == 1 {
-                match dir {
-                    1 => self.pos.1 += 1,
-                    2 => self.pos.1 -= 1,
-                    3 => self.pos.0 -= 1,
-                    4 => self.pos.0 += 1,
-                    _ => (),
+        let mut seen: HashSet<Node> = HashSet::new();
+        let mut queue = VecDeque::new();
+        seen.insert((0, 0));
+        self.map.insert((0, 0));
+        queue.push_back(((0, 0), self.computer.clone()));
+
+        while let Some((pos, droid)) = queue.pop_front() {
+            for dir in 1..5 {
+                let new_node = neighbour_node(&pos, dir);
+                if !seen.insert(new_node) {
+                    continue;
+                }
+                // Try to move
+                let mut fork = droid.clone();
+                fork.input(dir);
+                fork.execute().unwrap();
+                match fork.get_next_output().unwrap() {
+                    0 => (),
+                    out => {
+                        if out == 2 {
+                            self.end = Some(new_node);
+                        }
+                        self.map.insert(new_node);
+                        queue.push_back((new_node, fork));
+                    }
                 }
             }
         }
@@ -91,9 +79,11 @@ impl Explorer {
                 })
                 .collect()
         };
+        // The path contains the starting node
         pathfinding::directed::bfs::bfs(&(0, 0), successors, success)
             .unwrap()
             .len()
+            - 1
     }
 
     pub fn viz(&self) -> String {
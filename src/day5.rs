@@ -9,7 +9,7 @@ fn generator_input(input: &str) -> Vec<i64> {
 fn part_one(input: &[i64]) -> i64 {
     let mut computer = Computer::new(input.to_vec());
     computer.input(1);
-    computer.execute();
+    computer.execute().unwrap();
     *computer.output.iter().last().unwrap()
 }
 
@@ -17,6 +17,6 @@ fn part_one(input: &[i64]) -> i64 {
 fn part_two(input: &[i64]) -> i64 {
     let mut computer = Computer::new(input.to_vec());
     computer.input(5);
-    computer.execute();
+    computer.execute().unwrap();
     *computer.output.iter().last().unwrap()
 }
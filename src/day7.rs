@@ -1,5 +1,12 @@
+use crate::intcode_computer::concurrent::run_async;
 use crate::intcode_computer::{parse_input, Computer};
+use futures::channel::mpsc;
+use futures::executor::LocalPool;
+use futures::task::LocalSpawnExt;
+use futures::StreamExt;
 use itertools::Itertools;
+use std::cell::Cell;
+use std::rc::Rc;
 
 #[aoc_generator(day7)]
 pub fn input_generator(input: &str) -> Vec<i64> {
@@ -11,37 +18,51 @@ fn solve_sequence(input: &[i64], sequence: Vec<i64>) -> i64 {
         let mut amp = Computer::new(input.to_vec());
         amp.input(sequence[idx]);
         amp.input(inp_signal);
-        amp.execute();
-        let out = amp.output[0];
-        out
+        amp.execute().unwrap();
+        amp.output[0]
     })
 }
 
 fn solve_sequence_feedback(input: &[i64], sequence: Vec<i64>) -> i64 {
-    let mut amps: Vec<Computer> = sequence
-        .iter()
-        .map(|i| {
-            let mut c = Computer::new(input.to_vec())
-                .halt_on_output()
-                .halt_on_missing_input();
-            c.input(*i);
-            c
-        })
-        .collect();
+    let (senders, receivers): (Vec<_>, Vec<_>) = (0..5).map(|_| mpsc::unbounded()).unzip();
+    for (sender, phase) in senders.iter().zip(sequence) {
+        sender.unbounded_send(phase).unwrap();
+    }
+    senders[0].unbounded_send(0).unwrap();
 
-    (0..)
-        .scan(0, |state, idx| {
-            let idx = idx % 5;
-            amps[idx].input(*state);
-            amps[idx].execute();
-            let out = amps[idx].output.pop_front();
-            if let Some(o) = out {
-                *state = o;
+    // The amplifiers run as tasks on the current thread. The last one outputs to a relay,
+    // keeping its last signal for the thrusters
+    let mut pool = LocalPool::new();
+    let (relay, mut relayed) = mpsc::unbounded();
+    let thrusters = Rc::new(Cell::new(0));
+    let last_signal = thrusters.clone();
+    let to_first = senders[0].clone();
+    pool.spawner()
+        .spawn_local(async move {
+            while let Some(signal) = relayed.next().await {
+                last_signal.set(signal);
+                let _ = to_first.unbounded_send(signal);
             }
-            out
         })
-        .last()
-        .unwrap()
+        .unwrap();
+    for (idx, receiver) in receivers.into_iter().enumerate() {
+        let next = if idx == 4 {
+            relay.clone()
+        } else {
+            senders[idx + 1].clone()
+        };
+        let amp = run_async(Computer::new(input.to_vec()), receiver, next);
+        pool.spawner()
+            .spawn_local(async move {
+                amp.await.unwrap();
+            })
+            .unwrap();
+    }
+    drop(relay);
+    drop(senders);
+
+    pool.run();
+    thrusters.get()
 }
 
 #[aoc(day7, part1)]
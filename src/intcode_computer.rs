@@ -1,4 +1,44 @@
-use std::collections::VecDeque;
+use num_bigint::BigInt;
+use num_traits::ToPrimitive;
+use rayon::prelude::*;
+use std::collections::{BTreeMap, VecDeque};
+use std::ops::RangeInclusive;
+use std::sync::Arc;
+use std::time::Duration;
+
+pub mod arithmetic;
+pub mod ascii;
+pub mod assembler;
+mod budget;
+pub mod cfg;
+pub mod concurrent;
+pub mod coverage;
+pub mod debugger;
+pub mod decompiler;
+pub mod disassembler;
+pub mod fast;
+#[cfg(test)]
+mod fixtures;
+pub mod fuzz;
+pub mod instruction_set;
+pub mod ir;
+mod memory;
+pub mod network;
+pub mod ports;
+pub mod profiler;
+pub mod snapshot;
+pub mod symbolic;
+pub mod trace;
+
+use arithmetic::Arithmetic;
+use budget::{Budget, Meter};
+use coverage::{Coverage, Hit};
+use instruction_set::{Effects, InstructionSet};
+use ir::BinOp;
+pub use memory::Memory;
+use ports::{InputPort, OutputPort, Ports};
+use profiler::{Profile, Sample};
+use trace::{Trace, TraceEntry};
 
 pub fn parse_input(input: &str) -> Vec<i64> {
     input
@@ -7,7 +47,68 @@ pub fn parse_input(input: &str) -> Vec<i64> {
         .collect()
 }
 
-#[derive(Debug)]
+/// Maximum number of instructions executed by `Computer::run_with_noun_verb`
+pub const NOUN_VERB_CYCLES: u64 = 1_000_000;
+
+/// Errors that can happen while executing an Intcode program
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ComputerError {
+    /// The opcode at the given address is not a known instruction
+    InvalidOpcode { address: usize, opcode: i64 },
+    /// The instruction at the given address uses an unknown parameter mode
+    InvalidParameterMode { address: usize, mode: i64 },
+    /// The instruction at the given address accesses a negative address
+    NegativeAddress { address: usize, target: i64 },
+    /// The instruction at the given address tries to write to an immediate operand
+    WriteToImmediate { address: usize },
+    /// The code pointer is outside of the loaded or written memory
+    PointerOutOfBounds { pointer: usize },
+    /// The instruction at the given address computes or uses a value beyond the range of an i64
+    Overflow { address: usize },
+    /// The instruction at the given address, from a custom instruction set, failed
+    Extension { address: usize, message: String },
+    /// The program stopped before halting, where it was expected to run to completion
+    NotHalted { state: RunState },
+}
+
+impl std::fmt::Display for ComputerError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            ComputerError::InvalidOpcode { address, opcode } => {
+                write!(f, "invalid opcode {} at address {}", opcode, address)
+            }
+            ComputerError::InvalidParameterMode { address, mode } => {
+                write!(f, "invalid parameter mode {} at address {}", mode, address)
+            }
+            ComputerError::NegativeAddress { address, target } => write!(
+                f,
+                "instruction at address {} accesses negative address {}",
+                address, target
+            ),
+            ComputerError::WriteToImmediate { address } => write!(
+                f,
+                "instruction at address {} writes to an immediate operand",
+                address
+            ),
+            ComputerError::PointerOutOfBounds { pointer } => {
+                write!(f, "pointer {} is out of bounds", pointer)
+            }
+            ComputerError::Overflow { address } => {
+                write!(f, "instruction at address {} overflows an i64", address)
+            }
+            ComputerError::Extension { address, message } => {
+                write!(f, "instruction at address {} failed: {}", address, message)
+            }
+            ComputerError::NotHalted { state } => {
+                write!(f, "program stopped before halting: {:?}", state)
+            }
+        }
+    }
+}
+
+impl std::error::Error for ComputerError {}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum Value {
     Immediate(i64),
     Position(usize),
@@ -15,28 +116,34 @@ pub enum Value {
 }
 
 impl Value {
-    pub fn evaluate(self, code: &Vec<i64>, relative_base: i64, write_mode: bool) -> i64 {
-        match self {
-            Value::Immediate(x) => x,
-            Value::Position(addr) if !write_mode => code[addr],
-            Value::Position(addr) if write_mode => addr as i64,
-            Value::Relative(addr) if !write_mode => code[(addr + relative_base) as usize],
-            Value::Relative(addr) if write_mode => addr + relative_base,
-            _ => unreachable!(),
+    /// Creates a value from its raw operand and parameter mode.
+    /// `address` is the address of the instruction, used for error reporting
+    pub fn from(val: i64, param: i64, address: usize) -> Result<Self, ComputerError> {
+        match param {
+            0 if val < 0 => Err(ComputerError::NegativeAddress {
+                address,
+                target: val,
+            }),
+            0 => Ok(Value::Position(val as usize)),
+            1 => Ok(Value::Immediate(val)),
+            2 => Ok(Value::Relative(val)),
+            mode => Err(ComputerError::InvalidParameterMode { address, mode }),
         }
     }
+}
 
-    pub fn from(val: i64, param: i64) -> Self {
-        match param {
-            0 => Value::Position(val as usize),
-            1 => Value::Immediate(val),
-            2 => Value::Relative(val),
-            _ => unimplemented!(),
+impl std::fmt::Display for Value {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Value::Immediate(x) => write!(f, "#{}", x),
+            Value::Position(addr) => write!(f, "[{}]", addr),
+            Value::Relative(offset) if *offset < 0 => write!(f, "[rb{}]", offset),
+            Value::Relative(offset) => write!(f, "[rb+{}]", offset),
         }
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub enum Instruction {
     Add(Value, Value, Value),
     Mul(Value, Value, Value),
@@ -48,83 +155,177 @@ pub enum Instruction {
     Equals(Value, Value, Value),
     SetRelativeBase(Value),
     Halt,
+    /// Instruction of an opcode registered in the computer's instruction set
+    Extended {
+        opcode: i64,
+        mnemonic: &'static str,
+        operands: Vec<Value>,
+    },
 }
 
 impl Instruction {
-    fn parse_instr(code: &Vec<i64>, pointer: usize) -> Self {
-        let i = code[pointer];
-        //println!("parse i {}", i);
-        let (opcode, p1, p2, p3) = match i {
-            0..=99 => (i, 0, 0, 0),
-            _ => {
-                let opcode = i % 100;
-                let param = i / 100;
-                let p3 = i / 10000;
-                let p2 = (param % 100) / 10;
-                let p1 = param % 10;
-                (opcode, p1, p2, p3)
-            }
+    /// Decodes the standard instruction at the given address
+    pub fn parse_instr(code: &Memory, pointer: usize) -> Result<Self, ComputerError> {
+        if !code.contains(pointer) {
+            return Err(ComputerError::PointerOutOfBounds { pointer });
+        }
+        Instruction::decode(code.get(pointer), pointer, |offset| {
+            code.get(pointer + offset)
+        })
+    }
+
+    /// Decodes the standard instruction `i` found at the given address, reading its
+    /// operands with `cell(offset)`
+    fn decode(
+        i: i64,
+        pointer: usize,
+        mut cell: impl FnMut(usize) -> i64,
+    ) -> Result<Self, ComputerError> {
+        let opcode = i % 100;
+        let invalid = ComputerError::InvalidOpcode {
+            address: pointer,
+            opcode: i,
         };
-        match opcode {
-            1 => Instruction::Add(
-                Value::from(code[pointer + 1], p1),
-                Value::from(code[pointer + 2], p2),
-                Value::from(code[pointer + 3], p3),
-            ),
-            2 => Instruction::Mul(
-                Value::from(code[pointer + 1], p1),
-                Value::from(code[pointer + 2], p2),
-                Value::from(code[pointer + 3], p3),
-            ),
-            3 => Instruction::Inp(Value::from(code[pointer + 1], p1)),
-            4 => Instruction::Out(Value::from(code[pointer + 1], p1)),
-            5 => Instruction::JumpIfTrue(
-                Value::from(code[pointer + 1], p1),
-                Value::from(code[pointer + 2], p2),
-            ),
-            6 => Instruction::JumpIfFalse(
-                Value::from(code[pointer + 1], p1),
-                Value::from(code[pointer + 2], p2),
-            ),
-            7 => Instruction::LessThan(
-                Value::from(code[pointer + 1], p1),
-                Value::from(code[pointer + 2], p2),
-                Value::from(code[pointer + 3], p3),
-            ),
-            8 => Instruction::Equals(
-                Value::from(code[pointer + 1], p1),
-                Value::from(code[pointer + 2], p2),
-                Value::from(code[pointer + 3], p3),
-            ),
-            9 => Instruction::SetRelativeBase(Value::from(code[pointer + 1], p1)),
+        let args = match instruction_set::standard_opcode(opcode) {
+            Some((_, args)) => args,
+            None => return Err(invalid),
+        };
+        let mut operands = [Value::Immediate(0); 3];
+        for offset in 1..=args {
+            operands[offset - 1] = Instruction::operand(i, offset, args, cell(offset), pointer)?;
+        }
+        let [a, b, c] = operands;
+        Ok(match opcode {
+            1 => Instruction::Add(a, b, c),
+            2 => Instruction::Mul(a, b, c),
+            3 => Instruction::Inp(a),
+            4 => Instruction::Out(a),
+            5 => Instruction::JumpIfTrue(a, b),
+            6 => Instruction::JumpIfFalse(a, b),
+            7 => Instruction::LessThan(a, b, c),
+            8 => Instruction::Equals(a, b, c),
+            9 => Instruction::SetRelativeBase(a),
             99 => Instruction::Halt,
-            x => {
-                println!("Unhandled {}", x);
-                unimplemented!()
-            }
+            _ => return Err(invalid),
+        })
+    }
+
+    /// Decodes the operand at the given offset of the instruction `i`, which has `count`
+    /// operands, from its raw value. The mode of a third and last operand is made of all
+    /// the remaining digits
+    fn operand(
+        i: i64,
+        offset: usize,
+        count: usize,
+        val: i64,
+        pointer: usize,
+    ) -> Result<Value, ComputerError> {
+        let modes = 10i64
+            .checked_pow(offset as u32 + 1)
+            .map_or(0, |digits| i / digits);
+        let mode = if offset == 3 && count == 3 {
+            modes
+        } else {
+            modes % 10
+        };
+        Value::from(val, mode, pointer)
+    }
+
+    /// Value of the opcode, in the two lowest digits of the instruction
+    pub fn opcode(&self) -> i64 {
+        match self {
+            Instruction::Add(_, _, _) => 1,
+            Instruction::Mul(_, _, _) => 2,
+            Instruction::Inp(_) => 3,
+            Instruction::Out(_) => 4,
+            Instruction::JumpIfTrue(_, _) => 5,
+            Instruction::JumpIfFalse(_, _) => 6,
+            Instruction::LessThan(_, _, _) => 7,
+            Instruction::Equals(_, _, _) => 8,
+            Instruction::SetRelativeBase(_) => 9,
+            Instruction::Halt => 99,
+            Instruction::Extended { opcode, .. } => *opcode,
         }
     }
 
     pub fn args_count(&self) -> usize {
         match self {
-            Instruction::Add(_, _, _) => 3,
-            Instruction::Mul(_, _, _) => 3,
-            Instruction::Inp(_) => 1,
-            Instruction::Out(_) => 1,
-            Instruction::JumpIfTrue(_, _) => 2,
-            Instruction::JumpIfFalse(_, _) => 2,
-            Instruction::LessThan(_, _, _) => 3,
-            Instruction::Equals(_, _, _) => 3,
-            Instruction::SetRelativeBase(_) => 1,
-            Instruction::Halt => 0,
+            Instruction::Extended { operands, .. } => operands.len(),
+            _ => instruction_set::standard_opcode(self.opcode()).map_or(0, |(_, args)| args),
+        }
+    }
+
+    /// Short name of the instruction, as used in listings
+    pub fn mnemonic(&self) -> &'static str {
+        match self {
+            Instruction::Extended { mnemonic, .. } => mnemonic,
+            _ => instruction_set::standard_opcode(self.opcode())
+                .map(|(mnemonic, _)| mnemonic)
+                .unwrap_or("???"),
+        }
+    }
+
+    /// Operand the instruction writes to, if any.
+    /// Unknown for the instructions of registered opcodes
+    pub fn destination(&self) -> Option<&Value> {
+        match self {
+            Instruction::Add(_, _, c)
+            | Instruction::Mul(_, _, c)
+            | Instruction::LessThan(_, _, c)
+            | Instruction::Equals(_, _, c) => Some(c),
+            Instruction::Inp(a) => Some(a),
+            _ => None,
+        }
+    }
+
+    /// Operands of the instruction, in order
+    pub fn operands(&self) -> Vec<&Value> {
+        match self {
+            Instruction::Add(a, b, c)
+            | Instruction::Mul(a, b, c)
+            | Instruction::LessThan(a, b, c)
+            | Instruction::Equals(a, b, c) => vec![a, b, c],
+            Instruction::JumpIfTrue(a, b) | Instruction::JumpIfFalse(a, b) => vec![a, b],
+            Instruction::Inp(a) | Instruction::Out(a) | Instruction::SetRelativeBase(a) => vec![a],
+            Instruction::Halt => vec![],
+            Instruction::Extended { operands, .. } => operands.iter().collect(),
+        }
+    }
+}
+
+impl std::fmt::Display for Instruction {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let operands: Vec<String> = self.operands().iter().map(|v| v.to_string()).collect();
+        if operands.is_empty() {
+            write!(f, "{}", self.mnemonic())
+        } else {
+            write!(f, "{} {}", self.mnemonic(), operands.join(", "))
         }
     }
 }
 
-#[derive(Debug)]
+/// Reason why the computer stopped executing
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RunState {
+    /// The program reached a `Halt` instruction
+    Halted,
+    /// The program is waiting on an `Inp` instruction and the input is empty
+    NeedsInput,
+    /// The program produced an output (only when `halt_on_output` is set)
+    ProducedOutput(i64),
+    /// The execution budget is spent. Executing again resumes with a new budget
+    BudgetExhausted,
+    /// The computer is back in a state it was in, without consuming input since
+    /// (only when detecting infinite loops)
+    InfiniteLoop,
+}
+
+/// Intcode computer. Cloning it is cheap, its memory being copied on write.
+/// The input and output ports are not carried over to the clones
+#[derive(Debug, Clone)]
 pub struct Computer {
     /// Data of the program
-    code: Vec<i64>,
+    code: Memory,
     /// Code pointer
     pointer: usize,
     /// Output of the computer
@@ -133,40 +334,100 @@ pub struct Computer {
     input: VecDeque<i64>,
     /// Whether or not the computer should halt execution on output or not
     halt_on_output: bool,
-    /// Whether the computer should halt execution on missing input or not
-    halt_on_missing_input: bool,
+    /// Whether the program has executed a `Halt` instruction
+    halted: bool,
     /// The relative base (day9)
     relative_base: i64,
+    /// Executed instructions, when recording a trace
+    trace: Option<Trace>,
+    /// Execution statistics, when profiling
+    profile: Option<Profile>,
+    /// Executed instructions and branch directions, when measuring coverage
+    coverage: Option<Coverage>,
+    /// Limits of each execution
+    budget: Budget,
+    /// Input and output ports plugged in the computer
+    ports: Ports,
+    /// Handling of the results beyond the range of an i64
+    arithmetic: Arithmetic,
+    /// Cells holding values beyond the range of an i64, with the `BigInt` arithmetic
+    big_cells: BTreeMap<usize, BigInt>,
+    /// Opcodes registered on top of the standard ones, if any
+    instruction_set: Option<Arc<InstructionSet>>,
+    /// Side effects of the last instruction of a registered opcode
+    effects: Effects,
 }
 
 impl Computer {
     /// Creates a new instance of the Intcode Computer
     pub fn new(code: Vec<i64>) -> Self {
         Computer {
-            code,
+            code: Memory::new(code),
             pointer: 0,
             output: VecDeque::new(),
             input: VecDeque::new(),
             halt_on_output: false,
-            halt_on_missing_input: false,
+            halted: false,
             relative_base: 0,
+            trace: None,
+            profile: None,
+            coverage: None,
+            budget: Budget::default(),
+            ports: Ports::default(),
+            arithmetic: Arithmetic::default(),
+            big_cells: BTreeMap::new(),
+            instruction_set: None,
+            effects: Effects::default(),
         }
     }
 
-    /// Manually sets an address to a value
+    /// Manually sets an address to a value, growing the memory if needed
     pub fn set(&mut self, addr: usize, value: i64) {
-        self.code[addr] = value;
+        self.big_cells.remove(&addr);
+        self.code.set(addr, value);
     }
 
-    /// Sets the size of the available memory
-    pub fn set_available_memory(mut self, memory: usize) -> Self {
-        self.code.resize(memory, 0);
-        self
+    /// Gets the value at the given addr. Values beyond the range of an i64 are truncated
+    /// to their lowest 64 bits
+    pub fn get(&self, addr: usize) -> i64 {
+        self.code.get(addr)
     }
 
-    /// Gets the value at the given addr
-    pub fn get(&self, addr: usize) -> i64 {
-        self.code[addr]
+    /// Gets the value at the given addr, including the values beyond the range of an i64
+    pub fn get_big(&self, addr: usize) -> BigInt {
+        match self.big_cells.get(&addr) {
+            Some(value) => value.clone(),
+            None => BigInt::from(self.code.get(addr)),
+        }
+    }
+
+    /// Runs a program with its noun and verb patched at addresses 1 and 2, as in day 2,
+    /// returning the value left at address 0. The program must halt within
+    /// `NOUN_VERB_CYCLES` instructions, without input
+    pub fn run_with_noun_verb(code: &[i64], noun: i64, verb: i64) -> Result<i64, ComputerError> {
+        let mut computer = Computer::new(code.to_vec()).with_cycle_budget(NOUN_VERB_CYCLES);
+        computer.set(1, noun);
+        computer.set(2, verb);
+        match computer.execute()? {
+            RunState::Halted => Ok(computer.get(0)),
+            state => Err(ComputerError::NotHalted { state }),
+        }
+    }
+
+    /// Finds the first noun and verb within the ranges for which `run_with_noun_verb` gives an
+    /// accepted value. The pairs are tried in parallel, runs failing with an error are skipped
+    pub fn find_noun_verb(
+        code: &[i64],
+        nouns: RangeInclusive<i64>,
+        verbs: RangeInclusive<i64>,
+        accept: impl Fn(i64) -> bool + Sync,
+    ) -> Option<(i64, i64)> {
+        nouns
+            .into_par_iter()
+            .flat_map(|noun| verbs.clone().into_par_iter().map(move |verb| (noun, verb)))
+            .find_first(|(noun, verb)| {
+                Computer::run_with_noun_verb(code, *noun, *verb).is_ok_and(&accept)
+            })
     }
 
     /// Adds a value to the input of the computer
@@ -182,84 +443,340 @@ impl Computer {
         self.output.iter()
     }
 
-    /// Sets the halt_on_output flag on that computer instance
+    /// Sets the halt_on_output flag on that computer instance.
+    /// Outputs are then handed back through `RunState::ProducedOutput` instead of being queued
     pub fn halt_on_output(mut self) -> Self {
         self.halt_on_output = true;
         self
     }
 
-    /// Sets the halt_on_output flag on that computer instance
-    pub fn halt_on_missing_input(mut self) -> Self {
-        self.halt_on_missing_input = true;
+    /// Sets how `Add` and `Mul` handle results beyond the range of an i64. Overflows are
+    /// errors by default
+    pub fn with_arithmetic(mut self, arithmetic: Arithmetic) -> Self {
+        self.arithmetic = arithmetic;
+        self
+    }
+
+    /// Executes the programs with the given instruction set, whose registered opcodes are
+    /// decoded as `Instruction::Extended`. The fast, IR and symbolic computers, the control
+    /// flow graphs and the decompiler only know the standard opcodes, and snapshots do not
+    /// hold the instruction set
+    pub fn with_instruction_set(mut self, instruction_set: InstructionSet) -> Self {
+        let instruction_set = Arc::new(instruction_set);
+        if let Some(coverage) = self.coverage.as_mut() {
+            coverage.instruction_set = Some(instruction_set.clone());
+        }
+        self.instruction_set = Some(instruction_set);
+        self
+    }
+
+    /// Opcodes known to the computer
+    pub fn instruction_set(&self) -> Option<&InstructionSet> {
+        self.instruction_set.as_deref()
+    }
+
+    /// Plugs a port the computer reads from once its input queue is empty
+    pub fn with_input_port(mut self, port: impl InputPort + 'static) -> Self {
+        self.ports.input = Some(Box::new(port));
         self
     }
 
-    /// Executes the next instruction. Returns false if the program has halted
-    pub fn step(&mut self) -> bool {
-        let instr = Instruction::parse_instr(&self.code, self.pointer);
+    /// Plugs a port receiving the outputs, instead of the output queue
+    pub fn with_output_port(mut self, port: impl OutputPort + 'static) -> Self {
+        self.ports.output = Some(Box::new(port));
+        self
+    }
+
+    /// Reads the next input, from the input queue first, then from the input port
+    fn next_input(&mut self) -> Option<i64> {
+        let input = &mut self.ports.input;
+        self.input
+            .pop_front()
+            .or_else(|| input.as_mut().and_then(|port| port.read()))
+    }
+
+    /// Sends an output to the output port, or to the output queue if there is none
+    fn emit(&mut self, value: i64) {
+        match self.ports.output.as_mut() {
+            Some(port) => port.write(value),
+            None => self.output.push_back(value),
+        }
+    }
+
+    /// Stops each execution after the given number of instructions
+    pub fn with_cycle_budget(mut self, cycles: u64) -> Self {
+        self.budget.cycles = Some(cycles);
+        self
+    }
+
+    /// Stops each execution once it lasted for the given duration
+    pub fn with_time_budget(mut self, time: Duration) -> Self {
+        self.budget.time = Some(time);
+        self
+    }
+
+    /// Stops the execution when the computer gets back to a previous state
+    /// (code pointer, relative base and memory) without consuming input
+    pub fn detect_infinite_loops(mut self) -> Self {
+        self.budget.detect_loops = true;
+        self
+    }
+
+    /// Records every executed instruction in a trace
+    pub fn record_trace(mut self) -> Self {
+        self.trace = Some(Trace::default());
+        self
+    }
+
+    /// The trace recorded so far, if recording
+    pub fn trace(&self) -> Option<&Trace> {
+        self.trace.as_ref()
+    }
+
+    /// Takes the trace recorded so far, stopping the recording
+    pub fn take_trace(&mut self) -> Option<Trace> {
+        self.trace.take()
+    }
+
+    /// Counts the executed instructions and memory accesses in a profile
+    pub fn record_profile(mut self) -> Self {
+        self.profile = Some(Profile::default());
+        self
+    }
+
+    /// The profile recorded so far, if profiling
+    pub fn profile(&self) -> Option<&Profile> {
+        self.profile.as_ref()
+    }
+
+    /// Takes the profile recorded so far, stopping the profiling
+    pub fn take_profile(&mut self) -> Option<Profile> {
+        self.profile.take()
+    }
+
+    /// Records the executed instructions and the directions taken by conditional jumps
+    pub fn record_coverage(mut self) -> Self {
+        self.coverage = Some(Coverage {
+            instruction_set: self.instruction_set.clone(),
+            ..Coverage::default()
+        });
+        self
+    }
+
+    /// The coverage recorded so far, if measuring it
+    pub fn coverage(&self) -> Option<&Coverage> {
+        self.coverage.as_ref()
+    }
+
+    /// Takes the coverage recorded so far, stopping the measure
+    pub fn take_coverage(&mut self) -> Option<Coverage> {
+        self.coverage.take()
+    }
+
+    /// Resolves the address targeted by a parameter, when writing
+    fn address(&self, value: &Value) -> Result<usize, ComputerError> {
+        let target = match value {
+            Value::Immediate(_) => {
+                return Err(ComputerError::WriteToImmediate {
+                    address: self.pointer,
+                })
+            }
+            Value::Position(addr) => *addr as i64,
+            Value::Relative(offset) => {
+                offset
+                    .checked_add(self.relative_base)
+                    .ok_or(ComputerError::Overflow {
+                        address: self.pointer,
+                    })?
+            }
+        };
+        if target < 0 {
+            return Err(ComputerError::NegativeAddress {
+                address: self.pointer,
+                target,
+            });
+        }
+        Ok(target as usize)
+    }
+
+    /// Reads the value of a parameter, which must fit in an i64
+    fn read(&self, value: &Value) -> Result<i64, ComputerError> {
+        match value {
+            Value::Immediate(x) => Ok(*x),
+            _ => {
+                let addr = self.address(value)?;
+                if !self.big_cells.is_empty() && self.big_cells.contains_key(&addr) {
+                    return Err(ComputerError::Overflow {
+                        address: self.pointer,
+                    });
+                }
+                Ok(self.code.get(addr))
+            }
+        }
+    }
+
+    /// Reads the value of a parameter, which may be beyond the range of an i64
+    fn read_big(&self, value: &Value) -> Result<BigInt, ComputerError> {
+        match value {
+            Value::Immediate(x) => Ok(BigInt::from(*x)),
+            _ => self.address(value).map(|addr| self.get_big(addr)),
+        }
+    }
+
+    /// Reads a jump condition. Values beyond the range of an i64 are not 0
+    fn read_condition(&self, value: &Value) -> Result<bool, ComputerError> {
+        if let Value::Immediate(x) = value {
+            return Ok(*x != 0);
+        }
+        let addr = self.address(value)?;
+        Ok(self.big_cells.contains_key(&addr) || self.code.get(addr) != 0)
+    }
+
+    /// Writes a value to the address targeted by a parameter
+    fn write(&mut self, value: &Value, val: i64) -> Result<(), ComputerError> {
+        let addr = self.address(value)?;
+        self.set(addr, val);
+        Ok(())
+    }
+
+    /// Writes a value that may be beyond the range of an i64
+    fn write_big(&mut self, value: &Value, val: BigInt) -> Result<(), ComputerError> {
+        if let Some(val) = val.to_i64() {
+            return self.write(value, val);
+        }
+        let addr = self.address(value)?;
+        self.code.set(addr, arithmetic::truncate(&val));
+        self.big_cells.insert(addr, val);
+        Ok(())
+    }
+
+    /// Executes an instruction combining two values, following the arithmetic policy
+    fn binary(&mut self, op: BinOp, a: &Value, b: &Value, c: &Value) -> Result<(), ComputerError> {
+        if self.big_cells.is_empty() {
+            let (x, y) = (self.read(a)?, self.read(b)?);
+            let val = match (op.apply(x, y), self.arithmetic) {
+                (Some(val), _) => val,
+                (None, Arithmetic::Checked) => {
+                    return Err(ComputerError::Overflow {
+                        address: self.pointer,
+                    })
+                }
+                (None, Arithmetic::Wrapping) => arithmetic::wrapping(op, x, y),
+                (None, Arithmetic::BigInt) => {
+                    return self.write_big(c, arithmetic::big(op, x.into(), y.into()))
+                }
+            };
+            return self.write(c, val);
+        }
+        let val = arithmetic::big(op, self.read_big(a)?, self.read_big(b)?);
+        self.write_big(c, val)
+    }
+
+    /// Converts a jump target to a code pointer
+    fn jump_target(&self, target: i64) -> Result<usize, ComputerError> {
+        if target < 0 {
+            return Err(ComputerError::NegativeAddress {
+                address: self.pointer,
+                target,
+            });
+        }
+        Ok(target as usize)
+    }
+
+    /// Executes the next instruction.
+    /// Returns the reason why the computer should stop, if any
+    pub fn step(&mut self) -> Result<Option<RunState>, ComputerError> {
+        let instr = self.current_instruction()?;
+        if self.trace.is_none() && self.profile.is_none() && self.coverage.is_none() {
+            return self.execute_instruction(instr);
+        }
+
+        let traced = self
+            .trace
+            .as_ref()
+            .map(|_| TraceEntry::before(self, &instr));
+        let sample = self.profile.as_ref().map(|_| Sample::before(self, &instr));
+        let hit = self.coverage.as_ref().map(|_| Hit::before(self, &instr));
+        let state = self.execute_instruction(instr)?;
+        // An input instruction waiting for input is not executed yet
+        if state != Some(RunState::NeedsInput) {
+            if let Some((mut entry, destination)) = traced {
+                entry.after(self, destination);
+                if let Some(trace) = self.trace.as_mut() {
+                    trace.push(entry);
+                }
+            }
+            if let (Some(sample), Some(profile)) = (sample, self.profile.as_mut()) {
+                profile.record(sample);
+            }
+            if let (Some(hit), Some(coverage)) = (hit, self.coverage.as_mut()) {
+                coverage.record(hit);
+            }
+        }
+        Ok(state)
+    }
+
+    /// Executes an instruction decoded at the code pointer
+    fn execute_instruction(
+        &mut self,
+        instr: Instruction,
+    ) -> Result<Option<RunState>, ComputerError> {
         let forward = instr.args_count();
         let mut change_pc = true;
         match instr {
-            Instruction::Add(a, b, c) => {
-                let dst = c.evaluate(&self.code, self.relative_base, true) as usize;
-                self.code[dst] = a.evaluate(&self.code, self.relative_base, false)
-                    + b.evaluate(&self.code, self.relative_base, false)
-            }
-            Instruction::Mul(a, b, c) => {
-                let dst = c.evaluate(&self.code, self.relative_base, true) as usize;
-                self.code[dst] = a.evaluate(&self.code, self.relative_base, false)
-                    * b.evaluate(&self.code, self.relative_base, false)
-            }
+            Instruction::Add(a, b, c) => self.binary(BinOp::Add, &a, &b, &c)?,
+            Instruction::Mul(a, b, c) => self.binary(BinOp::Mul, &a, &b, &c)?,
             Instruction::Inp(a) => {
-                let inp = self.input.pop_front();
-                if let Some(i) = inp {
-                    let dst = a.evaluate(&self.code, self.relative_base, true) as usize;
-                    self.code[dst] = i;
+                // Check the destination before consuming the input
+                self.address(&a)?;
+                if let Some(i) = self.next_input() {
+                    self.write(&a, i)?;
                 } else {
-                    return !self.halt_on_missing_input;
+                    return Ok(Some(RunState::NeedsInput));
                 }
             }
             Instruction::Out(a) => {
-                self.output
-                    .push_back(a.evaluate(&self.code, self.relative_base, false));
+                let val = self.read(&a)?;
                 self.pointer += forward + 1;
-                return !self.halt_on_output;
+                if self.halt_on_output {
+                    return Ok(Some(RunState::ProducedOutput(val)));
+                }
+                self.emit(val);
+                return Ok(None);
             }
             Instruction::JumpIfTrue(a, b) => {
-                if a.evaluate(&self.code, self.relative_base, false) != 0 {
-                    self.pointer = b.evaluate(&self.code, self.relative_base, false) as usize;
+                if self.read_condition(&a)? {
+                    self.pointer = self.jump_target(self.read(&b)?)?;
                     change_pc = false;
                 }
             }
             Instruction::JumpIfFalse(a, b) => {
-                if a.evaluate(&self.code, self.relative_base, false) == 0 {
-                    self.pointer = b.evaluate(&self.code, self.relative_base, false) as usize;
+                if !self.read_condition(&a)? {
+                    self.pointer = self.jump_target(self.read(&b)?)?;
                     change_pc = false;
                 }
             }
-            Instruction::LessThan(a, b, c) => {
-                let a = a.evaluate(&self.code, self.relative_base, false);
-                let b = b.evaluate(&self.code, self.relative_base, false);
-                let dst = c.evaluate(&self.code, self.relative_base, true) as usize;
-                self.code[dst] = if a < b { 1 } else { 0 }
-            }
-            Instruction::Equals(a, b, c) => {
-                let a = a.evaluate(&self.code, self.relative_base, false);
-                let b = b.evaluate(&self.code, self.relative_base, false);
-                let dst = c.evaluate(&self.code, self.relative_base, true) as usize;
-                self.code[dst] = if a == b { 1 } else { 0 }
-            }
+            Instruction::LessThan(a, b, c) => self.binary(BinOp::LessThan, &a, &b, &c)?,
+            Instruction::Equals(a, b, c) => self.binary(BinOp::Equals, &a, &b, &c)?,
             Instruction::SetRelativeBase(offset) => {
-                self.relative_base += offset.evaluate(&self.code, self.relative_base, false);
+                self.relative_base = self.relative_base.checked_add(self.read(&offset)?).ok_or(
+                    ComputerError::Overflow {
+                        address: self.pointer,
+                    },
+                )?;
             }
             Instruction::Halt => {
-                return false;
+                self.halted = true;
+                return Ok(Some(RunState::Halted));
             }
+            Instruction::Extended {
+                opcode, operands, ..
+            } => return self.execute_extended(opcode, &operands),
         }
         if change_pc {
             self.pointer += forward + 1;
         }
-        true
+        Ok(None)
     }
 
     /// Displays the current code
@@ -275,41 +792,192 @@ impl Computer {
         self.output = VecDeque::new();
     }
 
-    /// Executes the whole program
-    pub fn execute(&mut self) {
+    /// Executes the program until it halts, needs input or produces an output,
+    /// or its budget is spent
+    pub fn execute(&mut self) -> Result<RunState, ComputerError> {
+        if !self.budget.is_limited() {
+            loop {
+                if let Some(state) = self.step()? {
+                    return Ok(state);
+                }
+            }
+        }
+
+        let mut meter = Meter::new(self.budget);
         loop {
-            if !self.step() {
-                break;
+            if let Some(state) = meter.tick(self) {
+                return Ok(state);
+            }
+            if let Some(state) = self.step()? {
+                return Ok(state);
             }
         }
     }
 
-    /// Tells whether the amp is halted for good or not
+    /// Tells whether the program has executed a `Halt` instruction
     pub fn halted(&self) -> bool {
-        return self.code[self.pointer] == 99;
+        self.halted
+    }
+
+    /// Current value of the code pointer
+    pub fn pointer(&self) -> usize {
+        self.pointer
+    }
+
+    /// Current value of the relative base
+    pub fn relative_base(&self) -> i64 {
+        self.relative_base
+    }
+
+    /// Values waiting to be read by `Inp` instructions
+    pub fn pending_input(&self) -> impl Iterator<Item = &i64> {
+        self.input.iter()
+    }
+
+    /// Memory of the computer
+    pub fn memory(&self) -> &Memory {
+        &self.code
+    }
+
+    /// Decodes the instruction at the code pointer, without executing it
+    pub fn current_instruction(&self) -> Result<Instruction, ComputerError> {
+        self.instruction_at(self.pointer)
+    }
+
+    /// Decodes the instruction at the given address, with the opcodes known to the computer
+    pub fn instruction_at(&self, addr: usize) -> Result<Instruction, ComputerError> {
+        match self.instruction_set.as_ref() {
+            Some(set) => set.decode(&self.code, addr),
+            None => Instruction::parse_instr(&self.code, addr),
+        }
     }
 }
 
 #[cfg(test)]
 pub mod tests {
-    use super::{parse_input, Computer};
+    use super::{parse_input, Computer, ComputerError, RunState};
 
     #[test]
     pub fn computer_test_case() {
         let input = parse_input("1002,4,3,4,33");
         let mut computer = Computer::new(input);
-        computer.step();
+        assert_eq!(computer.step().unwrap(), None);
         assert_eq!(computer.get(4), 99);
-        assert!(!computer.step());
+        assert!(!computer.halted());
+        assert_eq!(computer.step().unwrap(), Some(RunState::Halted));
+        assert!(computer.halted());
+    }
+
+    #[test]
+    pub fn computer_run_states() {
+        // Echoes its input twice, then halts
+        let input = parse_input("3,9,4,9,3,9,4,9,99,0");
+        let mut computer = Computer::new(input.clone());
+        assert_eq!(computer.execute(), Ok(RunState::NeedsInput));
+        computer.input(3);
+        assert_eq!(computer.execute(), Ok(RunState::NeedsInput));
+        computer.input(7);
+        assert_eq!(computer.execute(), Ok(RunState::Halted));
+        assert_eq!(computer.output, vec![3, 7]);
+
+        let mut computer = Computer::new(input).halt_on_output();
+        computer.input(1);
+        computer.input(2);
+        assert_eq!(computer.execute(), Ok(RunState::ProducedOutput(1)));
+        assert_eq!(computer.execute(), Ok(RunState::ProducedOutput(2)));
+        // The pointer now sits on the 99, but it has not been executed yet
+        assert!(!computer.halted());
+        assert_eq!(computer.execute(), Ok(RunState::Halted));
+        assert!(computer.halted());
+        assert!(computer.output.is_empty());
+    }
+
+    #[test]
+    pub fn computer_errors() {
+        let mut computer = Computer::new(parse_input("1,0,0,0,42"));
+        computer.step().unwrap();
+        assert_eq!(
+            computer.step(),
+            Err(ComputerError::InvalidOpcode {
+                address: 4,
+                opcode: 42
+            })
+        );
+
+        let mut computer = Computer::new(parse_input("301,0,0,0,99"));
+        assert_eq!(
+            computer.execute(),
+            Err(ComputerError::InvalidParameterMode {
+                address: 0,
+                mode: 3
+            })
+        );
+
+        let mut computer = Computer::new(parse_input("1,-1,0,0,99"));
+        assert_eq!(
+            computer.execute(),
+            Err(ComputerError::NegativeAddress {
+                address: 0,
+                target: -1
+            })
+        );
+
+        let mut computer = Computer::new(parse_input("10001,0,0,0,99"));
+        assert_eq!(
+            computer.execute(),
+            Err(ComputerError::WriteToImmediate { address: 0 })
+        );
+
+        // Operands past the end of the program read as 0
+        let mut computer = Computer::new(parse_input("1,0,0"));
+        assert_eq!(
+            computer.execute(),
+            Err(ComputerError::PointerOutOfBounds { pointer: 4 })
+        );
+        assert_eq!(computer.get(0), 2);
+
+        // Hostile programs: overflowing results, relative base and relative address,
+        // then a jump far past the end of the memory
+        for (code, address) in &[
+            ("1101,9223372036854775807,1,0,99", 0),
+            ("1102,9223372036854775807,2,0,99", 0),
+            ("109,9223372036854775807,109,1,99", 2),
+            ("109,-1,21101,0,0,-9223372036854775808,99", 2),
+        ] {
+            let mut computer = Computer::new(parse_input(code));
+            assert_eq!(
+                computer.execute(),
+                Err(ComputerError::Overflow { address: *address })
+            );
+        }
+        let mut computer = Computer::new(parse_input("1105,1,9223372036854775807"));
+        assert_eq!(
+            computer.execute(),
+            Err(ComputerError::PointerOutOfBounds {
+                pointer: i64::MAX as usize
+            })
+        );
+        assert!(!computer.halted());
     }
 
     // TODO: add day5 unit test
 
+    #[test]
+    pub fn computer_growing_memory() {
+        // Writes 5 far past the end of the program, then outputs it
+        let input = parse_input("1101,2,3,100000,4,100000,99");
+        let mut computer = Computer::new(input);
+        assert_eq!(computer.execute(), Ok(RunState::Halted));
+        assert_eq!(computer.get_next_output(), Some(5));
+        assert_eq!(computer.get(100000), 5);
+        assert_eq!(computer.get(99999), 0);
+    }
+
     #[test]
     pub fn computer_day9_tests() {
         let input = parse_input("109,1,204,-1,1001,100,1,100,1008,100,16,101,1006,101,0,99");
-        let mut computer = Computer::new(input).set_available_memory(150);
-        computer.execute();
+        let mut computer = Computer::new(input);
+        computer.execute().unwrap();
         let output: Vec<i64> = computer.get_all_output().cloned().collect();
         assert_eq!(
             output,
@@ -317,14 +985,14 @@ pub mod tests {
         );
 
         let input = parse_input("1102,34915192,34915192,7,4,7,99,0");
-        let mut computer = Computer::new(input).set_available_memory(150);
-        computer.execute();
+        let mut computer = Computer::new(input);
+        computer.execute().unwrap();
         let nb = computer.get_next_output().unwrap();
         assert_eq!(nb.to_string().len(), 16);
 
         let input = parse_input("104,1125899906842624,99");
         let mut computer = Computer::new(input);
-        computer.execute();
+        computer.execute().unwrap();
         assert_eq!(computer.get_next_output().unwrap(), 1125899906842624);
     }
 }
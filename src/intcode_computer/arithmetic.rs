@@ -0,0 +1,127 @@
+use super::ir::BinOp;
+use num_bigint::{BigInt, Sign};
+use serde::{Deserialize, Serialize};
+
+/// How a computer handles `Add` and `Mul` results beyond the range of an i64
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum Arithmetic {
+    /// Stops on a `ComputerError::Overflow`
+    #[default]
+    Checked,
+    /// Wraps around, in two's complement
+    Wrapping,
+    /// Stores the results in big integer cells. Big values can be added, multiplied and compared,
+    /// using one as an address, a jump target, a relative base offset or an output is an overflow
+    BigInt,
+}
+
+/// Result of an operation wrapping around on overflow
+pub(super) fn wrapping(op: BinOp, a: i64, b: i64) -> i64 {
+    match op {
+        BinOp::Add => a.wrapping_add(b),
+        BinOp::Mul => a.wrapping_mul(b),
+        _ => op.apply(a, b).unwrap(),
+    }
+}
+
+/// Result of an operation on big integers
+pub(super) fn big(op: BinOp, a: BigInt, b: BigInt) -> BigInt {
+    match op {
+        BinOp::Add => a + b,
+        BinOp::Mul => a * b,
+        BinOp::LessThan => BigInt::from((a < b) as i64),
+        BinOp::Equals => BigInt::from((a == b) as i64),
+    }
+}
+
+/// Lowest 64 bits of a big integer, in two's complement
+pub(super) fn truncate(value: &BigInt) -> i64 {
+    let bytes = value.to_signed_bytes_le();
+    let fill = if value.sign() == Sign::Minus { 0xff } else { 0 };
+    let mut low = [fill; 8];
+    for (byte, b) in low.iter_mut().zip(bytes.iter()) {
+        *byte = *b;
+    }
+    i64::from_le_bytes(low)
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::Arithmetic;
+    use crate::intcode_computer::snapshot::Snapshot;
+    use crate::intcode_computer::{parse_input, Computer, ComputerError};
+    use num_bigint::BigInt;
+
+    /// Computes [9] = [10] * [11] + [12], then halts
+    fn program(a: i64, b: i64, c: i64) -> Vec<i64> {
+        vec![2, 10, 11, 9, 1, 9, 12, 9, 99, 0, a, b, c]
+    }
+
+    #[test]
+    fn arithmetic_at_the_i64_boundaries() {
+        let run = |code: Vec<i64>, arithmetic| {
+            let mut computer = Computer::new(code).with_arithmetic(arithmetic);
+            computer.execute().map(|_| computer)
+        };
+
+        let fits = program(i64::MAX / 2, 2, 1);
+        for arithmetic in [
+            Arithmetic::Checked,
+            Arithmetic::Wrapping,
+            Arithmetic::BigInt,
+        ] {
+            let computer = run(fits.clone(), arithmetic).unwrap();
+            assert_eq!(computer.get(9), i64::MAX);
+            assert_eq!(computer.get_big(9), BigInt::from(i64::MAX));
+        }
+
+        let overflows = program(i64::MAX / 2, 2, 2);
+        assert_eq!(
+            run(overflows.clone(), Arithmetic::Checked).map(|_| ()),
+            Err(ComputerError::Overflow { address: 4 })
+        );
+        let computer = run(overflows.clone(), Arithmetic::Wrapping).unwrap();
+        assert_eq!(computer.get(9), i64::MIN);
+        let computer = run(overflows, Arithmetic::BigInt).unwrap();
+        assert_eq!(computer.get_big(9), BigInt::from(i64::MAX) + 1);
+        assert_eq!(computer.get(9), i64::MIN);
+
+        let underflows = program(i64::MIN, 1, -1);
+        assert_eq!(
+            run(underflows.clone(), Arithmetic::Checked).map(|_| ()),
+            Err(ComputerError::Overflow { address: 4 })
+        );
+        let computer = run(underflows.clone(), Arithmetic::Wrapping).unwrap();
+        assert_eq!(computer.get(9), i64::MAX);
+        let computer = run(underflows, Arithmetic::BigInt).unwrap();
+        assert_eq!(computer.get_big(9), BigInt::from(i64::MIN) - 1);
+
+        // Squares i64::MIN, compares it with itself, then outputs it
+        let code = parse_input("2,13,13,14,8,14,14,15,4,15,4,14,99,-9223372036854775808,0,0");
+        let mut computer = Computer::new(code).with_arithmetic(Arithmetic::BigInt);
+        assert_eq!(
+            computer.execute(),
+            Err(ComputerError::Overflow { address: 10 })
+        );
+        assert_eq!(computer.get_big(14), BigInt::from(i64::MIN) * i64::MIN);
+        assert_eq!(computer.output, vec![1]);
+
+        // A jump on a relative condition whose address overflows
+        for arithmetic in [
+            Arithmetic::Checked,
+            Arithmetic::Wrapping,
+            Arithmetic::BigInt,
+        ] {
+            let code = vec![109, i64::MAX, 1205, 1, 7, 104, 1, 99];
+            assert_eq!(
+                run(code, arithmetic).map(|_| ()),
+                Err(ComputerError::Overflow { address: 2 })
+            );
+        }
+
+        let snapshot = Snapshot::from_json(&computer.snapshot().to_json()).unwrap();
+        let restored = Computer::restore(snapshot).unwrap();
+        assert_eq!(restored.get_big(14), computer.get_big(14));
+        assert_eq!(restored.snapshot(), computer.snapshot());
+    }
+}
@@ -0,0 +1,124 @@
+use super::Computer;
+
+/// Whether an output value is an ASCII code, rather than a raw value such as an answer
+pub fn is_ascii(value: i64) -> bool {
+    (0..128).contains(&value)
+}
+
+/// Output of an ASCII program, its text separated from its non-ASCII values
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct AsciiOutput {
+    pub text: String,
+    pub values: Vec<i64>,
+}
+
+impl Computer {
+    /// Feeds the ASCII codes of the text to the computer
+    pub fn input_text(&mut self, text: &str) {
+        for c in text.chars() {
+            self.input(c as i64);
+        }
+    }
+
+    /// Feeds a line of text to the computer, followed by a newline
+    pub fn input_line(&mut self, line: &str) {
+        self.input_text(line);
+        self.input('\n' as i64);
+    }
+
+    /// Feeds several lines of text to the computer, each one followed by a newline
+    pub fn input_lines<'a>(&mut self, lines: impl IntoIterator<Item = &'a str>) {
+        for line in lines {
+            self.input_line(line);
+        }
+    }
+
+    /// Reads the output as text, until a newline, a non-ASCII value or the end of the output,
+    /// as for a prompt. The newline is consumed but not returned.
+    /// Returns None if there is no text at the start of the output
+    pub fn read_line(&mut self) -> Option<String> {
+        let mut line = String::new();
+        while let Some(value) = self.output.front().cloned() {
+            if !is_ascii(value) {
+                break;
+            }
+            self.output.pop_front();
+            match value as u8 as char {
+                '\n' => return Some(line),
+                c => line.push(c),
+            }
+        }
+        if line.is_empty() {
+            None
+        } else {
+            Some(line)
+        }
+    }
+
+    /// Consumes the whole output, separating its text from its non-ASCII values
+    pub fn take_ascii_output(&mut self) -> AsciiOutput {
+        let mut output = AsciiOutput::default();
+        for value in self.output.drain(..) {
+            if is_ascii(value) {
+                output.text.push(value as u8 as char);
+            } else {
+                output.values.push(value);
+            }
+        }
+        output
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::AsciiOutput;
+    use crate::intcode_computer::assembler::assemble;
+    use crate::intcode_computer::{Computer, RunState};
+
+    #[test]
+    fn ascii_prompt_and_answer() {
+        // Echoes a line after a prompt, then outputs 1000 + the length of the line
+        let code = assemble(
+            "
+                    OUT #62
+                    OUT #32
+            loop:   INP [c]
+                    EQ [c], #10, [tmp]
+                    JT [tmp], #done
+                    OUT [c]
+                    ADD [len], #1, [len]
+                    JT #1, #loop
+            done:   OUT #10
+                    ADD [len], #1000, [len]
+                    OUT [len]
+                    HLT
+            c:      DB 0
+            tmp:    DB 0
+            len:    DB 0
+            ",
+        )
+        .unwrap();
+
+        let mut computer = Computer::new(code.clone());
+        assert_eq!(computer.execute(), Ok(RunState::NeedsInput));
+        assert_eq!(computer.read_line(), Some("> ".to_string()));
+        assert_eq!(computer.read_line(), None);
+        computer.input_line("hello");
+        assert_eq!(computer.execute(), Ok(RunState::Halted));
+        assert_eq!(computer.read_line(), Some("hello".to_string()));
+        assert_eq!(computer.read_line(), None);
+        assert_eq!(computer.get_next_output(), Some(1005));
+
+        let mut computer = Computer::new(code);
+        computer.input_lines(vec!["abc", "ignored"]);
+        assert_eq!(computer.execute(), Ok(RunState::Halted));
+        assert_eq!(
+            computer.take_ascii_output(),
+            AsciiOutput {
+                text: "> abc\n".to_string(),
+                values: vec![1003]
+            }
+        );
+        assert!(computer.output.is_empty());
+    }
+}
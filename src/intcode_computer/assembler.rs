@@ -0,0 +1,434 @@
+use super::instruction_set::InstructionSet;
+use std::collections::HashMap;
+
+/// Errors that can happen while assembling a program. Lines are numbered from 1
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AssemblerError {
+    /// The mnemonic is neither an instruction nor a directive
+    UnknownMnemonic { line: usize, mnemonic: String },
+    /// The operand could not be parsed
+    InvalidOperand { line: usize, operand: String },
+    /// The instruction does not have the right number of operands
+    WrongOperandCount {
+        line: usize,
+        expected: usize,
+        found: usize,
+    },
+    /// The operand refers to a label that is never defined
+    UndefinedLabel { line: usize, label: String },
+    /// The label is defined twice
+    DuplicateLabel { line: usize, label: String },
+    /// The instruction writes to an immediate operand
+    ImmediateDestination { line: usize },
+}
+
+impl std::fmt::Display for AssemblerError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            AssemblerError::UnknownMnemonic { line, mnemonic } => {
+                write!(f, "line {}: unknown mnemonic {}", line, mnemonic)
+            }
+            AssemblerError::InvalidOperand { line, operand } => {
+                write!(f, "line {}: invalid operand {}", line, operand)
+            }
+            AssemblerError::WrongOperandCount {
+                line,
+                expected,
+                found,
+            } => write!(
+                f,
+                "line {}: expected {} operands, found {}",
+                line, expected, found
+            ),
+            AssemblerError::UndefinedLabel { line, label } => {
+                write!(f, "line {}: undefined label {}", line, label)
+            }
+            AssemblerError::DuplicateLabel { line, label } => {
+                write!(f, "line {}: label {} is already defined", line, label)
+            }
+            AssemblerError::ImmediateDestination { line } => {
+                write!(f, "line {}: cannot write to an immediate operand", line)
+            }
+        }
+    }
+}
+
+impl std::error::Error for AssemblerError {}
+
+/// A number, or a label with an optional offset
+#[derive(Debug, Clone)]
+enum Expr {
+    Number(i64),
+    Label(String, i64),
+}
+
+impl std::fmt::Display for Expr {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Expr::Number(n) => write!(f, "{}", n),
+            Expr::Label(label, 0) => write!(f, "{}", label),
+            Expr::Label(label, offset) => write!(f, "{}{:+}", label, offset),
+        }
+    }
+}
+
+/// An operand, with its parameter mode
+#[derive(Debug, Clone)]
+enum Operand {
+    Position(Expr),
+    Immediate(Expr),
+    Relative(Expr),
+}
+
+impl Operand {
+    fn mode(&self) -> i64 {
+        match self {
+            Operand::Position(_) => 0,
+            Operand::Immediate(_) => 1,
+            Operand::Relative(_) => 2,
+        }
+    }
+
+    /// Parameter mode, shifted to the digit of the operand at the given index
+    fn mode_digit(&self, index: usize) -> Option<i64> {
+        match self.mode() {
+            0 => Some(0),
+            mode => 10i64.checked_pow(index as u32 + 2)?.checked_mul(mode),
+        }
+    }
+
+    fn expr(&self) -> &Expr {
+        match self {
+            Operand::Position(e) | Operand::Immediate(e) | Operand::Relative(e) => e,
+        }
+    }
+}
+
+impl std::fmt::Display for Operand {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Operand::Position(e) => write!(f, "[{}]", e),
+            Operand::Immediate(e) => write!(f, "#{}", e),
+            Operand::Relative(Expr::Number(n)) if *n < 0 => write!(f, "[rb{}]", n),
+            Operand::Relative(e) => write!(f, "[rb+{}]", e),
+        }
+    }
+}
+
+/// A parsed line of the source
+#[derive(Debug)]
+enum Statement {
+    Instruction { opcode: i64, operands: Vec<Operand> },
+    Data(Vec<Expr>),
+}
+
+impl Statement {
+    fn size(&self) -> usize {
+        match self {
+            Statement::Instruction { operands, .. } => operands.len() + 1,
+            Statement::Data(values) => values.len(),
+        }
+    }
+}
+
+fn is_label(name: &str) -> bool {
+    let mut chars = name.chars();
+    match chars.next() {
+        Some(c) if c.is_ascii_alphabetic() || c == '_' => {
+            chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+        }
+        _ => false,
+    }
+}
+
+/// Parses `42`, `-3`, `label`, `label+2` or `label-1`
+fn parse_expr(text: &str) -> Option<Expr> {
+    let text = text.trim();
+    if let Ok(n) = text.parse::<i64>() {
+        return Some(Expr::Number(n));
+    }
+    let (label, offset) = match text.find(['+', '-']) {
+        Some(idx) => (
+            text[..idx].trim(),
+            text[idx..]
+                .replace(' ', "")
+                .trim_start_matches('+')
+                .parse()
+                .ok()?,
+        ),
+        None => (text, 0),
+    };
+    if is_label(label) {
+        Some(Expr::Label(label.to_string(), offset))
+    } else {
+        None
+    }
+}
+
+/// Parses `#expr`, `[expr]`, `[rb]`, `[rb+n]` or `[rb-n]`
+fn parse_operand(text: &str) -> Option<Operand> {
+    let text = text.trim();
+    if let Some(value) = text.strip_prefix('#') {
+        return parse_expr(value).map(Operand::Immediate);
+    }
+    let inner = text.strip_prefix('[')?.strip_suffix(']')?.trim();
+    if inner.eq_ignore_ascii_case("rb") {
+        return Some(Operand::Relative(Expr::Number(0)));
+    }
+    if inner.len() > 2 && inner[..2].eq_ignore_ascii_case("rb") {
+        let rest = inner[2..].trim_start();
+        if rest.starts_with('+') || rest.starts_with('-') {
+            let offset = parse_expr(rest.trim_start_matches('+'))?;
+            return Some(Operand::Relative(offset));
+        }
+    }
+    parse_expr(inner).map(Operand::Position)
+}
+
+fn parse_statement(
+    line: usize,
+    text: &str,
+    set: &InstructionSet,
+) -> Result<Statement, AssemblerError> {
+    let (mnemonic, rest) = match text.find(char::is_whitespace) {
+        Some(idx) => (&text[..idx], text[idx..].trim()),
+        None => (text, ""),
+    };
+    let args: Vec<&str> = if rest.is_empty() {
+        vec![]
+    } else {
+        rest.split(',').collect()
+    };
+    let mnemonic = mnemonic.to_ascii_uppercase();
+
+    if mnemonic == "DB" {
+        return args
+            .iter()
+            .map(|a| {
+                parse_expr(a).ok_or_else(|| AssemblerError::InvalidOperand {
+                    line,
+                    operand: a.trim().to_string(),
+                })
+            })
+            .collect::<Result<Vec<_>, _>>()
+            .map(Statement::Data);
+    }
+
+    let (opcode, count, standard) = set
+        .by_mnemonic(&mnemonic)
+        .map(|(opcode, def)| (opcode, def.args, def.is_standard()))
+        .ok_or(AssemblerError::UnknownMnemonic { line, mnemonic })?;
+    if args.len() != count {
+        return Err(AssemblerError::WrongOperandCount {
+            line,
+            expected: count,
+            found: args.len(),
+        });
+    }
+    let operands = args
+        .iter()
+        .map(|a| {
+            parse_operand(a).ok_or_else(|| AssemblerError::InvalidOperand {
+                line,
+                operand: a.trim().to_string(),
+            })
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+
+    // INP writes to its only operand, ADD, MUL, LT and EQ to their third one
+    let destination = match opcode {
+        3 if standard => operands.first(),
+        1 | 2 | 7 | 8 if standard => operands.get(2),
+        _ => None,
+    };
+    if let Some(Operand::Immediate(_)) = destination {
+        return Err(AssemblerError::ImmediateDestination { line });
+    }
+    Ok(Statement::Instruction { opcode, operands })
+}
+
+fn resolve(
+    line: usize,
+    expr: &Expr,
+    labels: &HashMap<String, usize>,
+) -> Result<i64, AssemblerError> {
+    match expr {
+        Expr::Number(n) => Ok(*n),
+        Expr::Label(label, offset) => {
+            let addr = labels
+                .get(label)
+                .ok_or_else(|| AssemblerError::UndefinedLabel {
+                    line,
+                    label: label.clone(),
+                })?;
+            (*addr as i64)
+                .checked_add(*offset)
+                .ok_or_else(|| AssemblerError::InvalidOperand {
+                    line,
+                    operand: expr.to_string(),
+                })
+        }
+    }
+}
+
+/// Assembles a program written with the mnemonics of the disassembler.
+///
+/// Each line holds an optional `label:`, then an optional instruction or `DB` directive,
+/// then an optional `;` comment. Operands are written `#5` (immediate), `[100]` (position)
+/// or `[rb+3]` (relative), and numbers can be replaced by `label` or `label+offset`.
+pub fn assemble(source: &str) -> Result<Vec<i64>, AssemblerError> {
+    assemble_with(source, &InstructionSet::standard())
+}
+
+/// Assembles a program using the mnemonics of the given instruction set
+pub fn assemble_with(source: &str, set: &InstructionSet) -> Result<Vec<i64>, AssemblerError> {
+    let mut labels: HashMap<String, usize> = HashMap::new();
+    let mut statements = vec![];
+    let mut addr = 0;
+
+    for (idx, text) in source.lines().enumerate() {
+        let line = idx + 1;
+        let mut text = text.split(';').next().unwrap_or("").trim();
+        if let Some(colon) = text.find(':') {
+            let label = text[..colon].trim();
+            if is_label(label) {
+                if labels.insert(label.to_string(), addr).is_some() {
+                    return Err(AssemblerError::DuplicateLabel {
+                        line,
+                        label: label.to_string(),
+                    });
+                }
+                text = text[colon + 1..].trim();
+            }
+        }
+        if text.is_empty() {
+            continue;
+        }
+        let statement = parse_statement(line, text, set)?;
+        addr += statement.size();
+        statements.push((line, statement));
+    }
+
+    let mut code = Vec::with_capacity(addr);
+    for (line, statement) in statements {
+        match statement {
+            Statement::Instruction { opcode, operands } => {
+                let mut cell = opcode;
+                for (i, operand) in operands.iter().enumerate() {
+                    cell = operand
+                        .mode_digit(i)
+                        .and_then(|digit| cell.checked_add(digit))
+                        .ok_or_else(|| AssemblerError::InvalidOperand {
+                            line,
+                            operand: operand.to_string(),
+                        })?;
+                }
+                code.push(cell);
+                for operand in operands {
+                    code.push(resolve(line, operand.expr(), &labels)?);
+                }
+            }
+            Statement::Data(values) => {
+                for value in values {
+                    code.push(resolve(line, &value, &labels)?);
+                }
+            }
+        }
+    }
+    Ok(code)
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::{assemble, assemble_with, AssemblerError};
+    use crate::intcode_computer::disassembler::disassemble;
+    use crate::intcode_computer::fixtures::QUINE;
+    use crate::intcode_computer::instruction_set::InstructionSet;
+    use crate::intcode_computer::{parse_input, Computer};
+
+    #[test]
+    fn assembler_program() {
+        let source = "
+            ; Outputs 3, 2, 1
+                    ARB #stack
+                    ADD #3, #0, [counter]
+            loop:   OUT [counter]            ; prints the counter
+                    ADD [counter], #-1, [counter]
+                    JT [counter], #loop
+                    HLT
+            counter: DB 0
+            stack:
+        ";
+        let code = assemble(source).unwrap();
+        assert_eq!(
+            code,
+            vec![109, 17, 1101, 3, 0, 16, 4, 16, 1001, 16, -1, 16, 1005, 16, 6, 99, 0]
+        );
+
+        let mut computer = Computer::new(code);
+        computer.execute().unwrap();
+        assert_eq!(computer.output, vec![3, 2, 1]);
+
+        assert_eq!(
+            assemble("INP #3"),
+            Err(AssemblerError::ImmediateDestination { line: 1 })
+        );
+        assert_eq!(
+            assemble("JT #1, #nowhere"),
+            Err(AssemblerError::UndefinedLabel {
+                line: 1,
+                label: "nowhere".to_string()
+            })
+        );
+        assert_eq!(
+            assemble("ADD #1, #2"),
+            Err(AssemblerError::WrongOperandCount {
+                line: 1,
+                expected: 3,
+                found: 2
+            })
+        );
+        assert_eq!(
+            assemble("\nDIV #1, #2, [0]"),
+            Err(AssemblerError::UnknownMnemonic {
+                line: 2,
+                mnemonic: "DIV".to_string()
+            })
+        );
+
+        // Label offsets and parameter modes beyond the range of an i64
+        assert_eq!(
+            assemble("DB 0\nend: DB end+9223372036854775807"),
+            Err(AssemblerError::InvalidOperand {
+                line: 2,
+                operand: "end+9223372036854775807".to_string()
+            })
+        );
+        let set = InstructionSet::standard()
+            .register(50, "WIDE", 18, |_| Ok(None))
+            .unwrap();
+        let operands = vec!["#0"; 18].join(", ");
+        assert_eq!(
+            assemble_with(&format!("WIDE {}", operands), &set),
+            Err(AssemblerError::InvalidOperand {
+                line: 1,
+                operand: "#0".to_string()
+            })
+        );
+    }
+
+    #[test]
+    fn assembler_round_trip() {
+        let programs = vec![
+            "1101,3,0,17,4,17,1001,17,-1,17,1005,17,4,99,7,8,9,0",
+            "109,20,21101,9,0,0,1105,1,10,99,204,0,2105,1,0",
+            QUINE,
+            "3,52,1001,52,-5,52,3,53,1,52,56,54,1007,54,5,55,1005,55,26,1001,54,-5,54,1105,1,12,1,53,54,53,1008,54,0,55,1001,55,1,55,2,53,55,53,4,53,1001,56,-1,56,1005,56,6,99,0,0,0,0,10",
+        ];
+        for program in programs {
+            let code = parse_input(program);
+            let listing = disassemble(&code).to_string();
+            assert_eq!(assemble(&listing), Ok(code));
+        }
+    }
+}
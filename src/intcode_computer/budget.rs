@@ -0,0 +1,156 @@
+use super::{Computer, Instruction, Memory, RunState};
+use std::time::{Duration, Instant};
+
+/// Number of instructions executed between two checks of the clock
+const CLOCK_PERIOD: u64 = 1024;
+
+/// Limits of a single call to `Computer::execute`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub(super) struct Budget {
+    /// Maximum number of executed instructions
+    pub cycles: Option<u64>,
+    /// Maximum duration of the execution
+    pub time: Option<Duration>,
+    /// Whether to stop when the state of the computer repeats
+    pub detect_loops: bool,
+}
+
+impl Budget {
+    pub fn is_limited(&self) -> bool {
+        self.cycles.is_some() || self.time.is_some() || self.detect_loops
+    }
+}
+
+/// State of the computer compared to detect loops
+#[derive(PartialEq, Eq)]
+struct LoopState {
+    pointer: usize,
+    relative_base: i64,
+    memory: Memory,
+}
+
+impl LoopState {
+    fn of(computer: &Computer) -> Self {
+        LoopState {
+            pointer: computer.pointer,
+            relative_base: computer.relative_base,
+            memory: computer.code.clone(),
+        }
+    }
+
+    fn matches(&self, computer: &Computer) -> bool {
+        // Comparing the memory is the costly part, and its clone is shared until written to
+        self.pointer == computer.pointer
+            && self.relative_base == computer.relative_base
+            && (self.memory.shares_cells(&computer.code) || self.memory == computer.code)
+    }
+}
+
+/// Consumption of a budget during a call to `Computer::execute`
+pub(super) struct Meter {
+    budget: Budget,
+    cycles: u64,
+    start: Instant,
+    /// State saved by Brent's cycle detection, and the steps since it was saved
+    saved: Option<LoopState>,
+    steps: u64,
+    power: u64,
+}
+
+impl Meter {
+    pub fn new(budget: Budget) -> Self {
+        Meter {
+            budget,
+            cycles: 0,
+            start: Instant::now(),
+            saved: None,
+            steps: 0,
+            power: 1,
+        }
+    }
+
+    /// Called before each instruction. Returns the state to stop the computer with, if any
+    pub fn tick(&mut self, computer: &Computer) -> Option<RunState> {
+        if self.budget.cycles == Some(self.cycles) {
+            return Some(RunState::BudgetExhausted);
+        }
+        if let Some(time) = self.budget.time {
+            if self.cycles.is_multiple_of(CLOCK_PERIOD) && self.start.elapsed() >= time {
+                return Some(RunState::BudgetExhausted);
+            }
+        }
+        self.cycles += 1;
+        if self.budget.detect_loops && self.repeats(computer) {
+            return Some(RunState::InfiniteLoop);
+        }
+        None
+    }
+
+    /// Whether the computer is back in a state it was in, since it last consumed input.
+    /// The state is saved after 1, 2, 4, 8... steps, so any loop is detected
+    /// within a few times its length. Consuming input starts the detection over
+    fn repeats(&mut self, computer: &Computer) -> bool {
+        if let Ok(Instruction::Inp(_)) = computer.current_instruction() {
+            self.saved = None;
+            self.steps = 0;
+            self.power = 1;
+        }
+        match self.saved.as_ref() {
+            Some(saved) if saved.matches(computer) => return true,
+            Some(_) if self.steps < self.power => self.steps += 1,
+            _ => {
+                self.saved = Some(LoopState::of(computer));
+                self.power = self.power.saturating_mul(2);
+                self.steps = 1;
+            }
+        }
+        false
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use crate::intcode_computer::assembler::assemble;
+    use crate::intcode_computer::{Computer, RunState};
+    use std::time::Duration;
+
+    #[test]
+    fn budget_cycles_time_and_loops() {
+        // Counts down from its input, then spins forever
+        let code = assemble(
+            "
+                    INP [counter]
+            loop:   ADD [counter], #-1, [counter]
+                    JT [counter], #loop
+            spin:   JT #1, #spin
+            counter: DB 0
+            ",
+        )
+        .unwrap();
+
+        let mut computer = Computer::new(code.clone()).with_cycle_budget(5);
+        computer.input(10);
+        assert_eq!(computer.execute(), Ok(RunState::BudgetExhausted));
+        assert_eq!(computer.get(12), 8);
+        assert_eq!(computer.execute(), Ok(RunState::BudgetExhausted));
+        assert_eq!(computer.get(12), 5);
+
+        let mut computer = Computer::new(code.clone()).detect_infinite_loops();
+        computer.input(1000);
+        assert_eq!(computer.execute(), Ok(RunState::InfiniteLoop));
+        assert_eq!(computer.get(12), 0);
+        assert_eq!(computer.pointer(), 9);
+
+        let mut computer = Computer::new(code).with_time_budget(Duration::from_millis(10));
+        computer.input(1);
+        assert_eq!(computer.execute(), Ok(RunState::BudgetExhausted));
+
+        // Reads inputs forever: each input starts the loop detection over
+        let mut computer = Computer::new(vec![3, 100, 1105, 1, 0]).detect_infinite_loops();
+        for input in 0..500 {
+            computer.input(input);
+        }
+        assert_eq!(computer.execute(), Ok(RunState::NeedsInput));
+        assert_eq!(computer.get(100), 499);
+    }
+}
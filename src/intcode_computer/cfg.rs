@@ -0,0 +1,298 @@
+use super::disassembler::{constant_store, disassemble, flow, is_unconditional_jump, Item};
+use super::{Instruction, Value};
+use std::collections::{BTreeMap, BTreeSet};
+use std::ops::Range;
+
+/// Sequence of instructions always executed together
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BasicBlock {
+    /// Instructions, with their address
+    pub instructions: Vec<(usize, Instruction)>,
+    /// Whether the block ends with a jump to an address only known at runtime
+    pub computed: bool,
+}
+
+impl BasicBlock {
+    pub fn start(&self) -> usize {
+        self.instructions[0].0
+    }
+
+    /// Address following the last instruction of the block
+    pub fn end(&self) -> usize {
+        let (addr, instr) = self.instructions.last().unwrap();
+        addr + instr.args_count() + 1
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EdgeKind {
+    /// Unconditional jump
+    Jump,
+    /// Conditional jump, when taken
+    Taken,
+    /// Conditional jump, when not taken
+    NotTaken,
+    /// The next block follows the last instruction, which is not a jump
+    FallThrough,
+    /// From a call to the address the function returns to
+    Return,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Edge {
+    pub from: usize,
+    pub to: usize,
+    pub kind: EdgeKind,
+}
+
+/// Write of an instruction into the code of the program
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SelfModification {
+    /// Address of the writing instruction
+    pub instruction: usize,
+    /// Written address, inside an instruction
+    pub target: usize,
+}
+
+/// Control flow graph of an Intcode image, with the code reachable from address 0.
+/// Writes through the relative base are not known statically, and are not reported
+/// as self-modifications
+#[derive(Debug, Clone)]
+pub struct Cfg {
+    /// Blocks, by start address
+    blocks: BTreeMap<usize, BasicBlock>,
+    edges: Vec<Edge>,
+    self_modifications: Vec<SelfModification>,
+    unreachable: Vec<Range<usize>>,
+}
+
+/// Tells whether an instruction ends a basic block
+fn ends_block(instr: &Instruction) -> bool {
+    let flow = flow(instr);
+    !flow.targets.is_empty() || flow.computed || !flow.falls_through
+}
+
+impl Cfg {
+    /// Builds the graph of an image, following the control flow as the disassembler does
+    pub fn build(code: &[i64]) -> Self {
+        let listing = disassemble(code);
+        let instructions: BTreeMap<usize, Instruction> = listing
+            .items()
+            .filter_map(|(addr, item)| match item {
+                Item::Code(instr) => Some((*addr, instr.clone())),
+                Item::Data(_) => None,
+            })
+            .collect();
+
+        let mut code_cells = vec![false; code.len()];
+        for (addr, instr) in instructions.iter() {
+            let end = (addr + instr.args_count() + 1).min(code.len());
+            code_cells[*addr..end].iter_mut().for_each(|cell| *cell = true);
+        }
+
+        // Blocks start at address 0, at jump targets and after jumps
+        let mut leaders: BTreeSet<usize> = listing.labels().cloned().collect();
+        leaders.insert(0);
+        for (addr, instr) in instructions.iter() {
+            if ends_block(instr) {
+                leaders.insert(addr + instr.args_count() + 1);
+            }
+        }
+
+        let mut blocks = BTreeMap::new();
+        let mut edges = vec![];
+        let mut current: Vec<(usize, Instruction)> = vec![];
+        for (addr, instr) in instructions.iter() {
+            let next = addr + instr.args_count() + 1;
+            current.push((*addr, instr.clone()));
+            if !ends_block(instr) && !leaders.contains(&next) && instructions.contains_key(&next) {
+                continue;
+            }
+            let start = current[0].0;
+            let instr_flow = flow(instr);
+            let conditional = instr_flow.falls_through && !instr_flow.targets.is_empty();
+            for target in instr_flow.targets.iter() {
+                let kind = if conditional {
+                    EdgeKind::Taken
+                } else {
+                    EdgeKind::Jump
+                };
+                edges.push(Edge {
+                    from: start,
+                    to: *target,
+                    kind,
+                });
+            }
+            if instr_flow.falls_through && instructions.contains_key(&next) {
+                let kind = if conditional || instr_flow.computed {
+                    EdgeKind::NotTaken
+                } else {
+                    EdgeKind::FallThrough
+                };
+                edges.push(Edge {
+                    from: start,
+                    to: next,
+                    kind,
+                });
+            }
+            // A call stores its return address right before jumping to the function
+            if is_unconditional_jump(instr) && current.len() > 1 {
+                let (_, store) = &current[current.len() - 2];
+                if constant_store(store) == Some(next as i64) {
+                    edges.push(Edge {
+                        from: start,
+                        to: next,
+                        kind: EdgeKind::Return,
+                    });
+                }
+            }
+            let block = BasicBlock {
+                instructions: std::mem::take(&mut current),
+                computed: instr_flow.computed,
+            };
+            blocks.insert(start, block);
+        }
+
+        let self_modifications = instructions
+            .iter()
+            .filter_map(|(addr, instr)| match instr.destination() {
+                Some(Value::Position(target)) if code_cells.get(*target) == Some(&true) => {
+                    Some(SelfModification {
+                        instruction: *addr,
+                        target: *target,
+                    })
+                }
+                _ => None,
+            })
+            .collect();
+
+        let mut unreachable: Vec<Range<usize>> = vec![];
+        for (cell, _) in code_cells.iter().enumerate().filter(|(_, c)| !**c) {
+            match unreachable.last_mut() {
+                Some(range) if range.end == cell => range.end += 1,
+                _ => unreachable.push(cell..cell + 1),
+            }
+        }
+
+        Cfg {
+            blocks,
+            edges,
+            self_modifications,
+            unreachable,
+        }
+    }
+
+    pub fn blocks(&self) -> impl Iterator<Item = &BasicBlock> {
+        self.blocks.values()
+    }
+
+    pub fn block(&self, start: usize) -> Option<&BasicBlock> {
+        self.blocks.get(&start)
+    }
+
+    pub fn edges(&self) -> &[Edge] {
+        &self.edges
+    }
+
+    /// Instructions writing at a fixed address inside the code
+    pub fn self_modifications(&self) -> &[SelfModification] {
+        &self.self_modifications
+    }
+
+    /// Ranges of cells that are never executed, usually data
+    pub fn unreachable(&self) -> &[Range<usize>] {
+        &self.unreachable
+    }
+
+    /// Exports the graph in the Graphviz DOT format.
+    /// Computed jumps point to a single `computed` node
+    pub fn to_dot(&self) -> String {
+        let mut dot = String::from("digraph cfg {\n");
+        dot.push_str("    node [shape=box, fontname=monospace];\n");
+        for (start, block) in self.blocks.iter() {
+            let lines: String = block
+                .instructions
+                .iter()
+                .map(|(addr, instr)| format!("{}: {}\\l", addr, instr))
+                .collect();
+            dot.push_str(&format!(
+                "    L{} [label=\"L{}\\l{}\"];\n",
+                start, start, lines
+            ));
+        }
+        if self.blocks.values().any(|b| b.computed) {
+            dot.push_str("    computed [shape=ellipse];\n");
+        }
+        for edge in self.edges.iter() {
+            let style = match edge.kind {
+                EdgeKind::Jump | EdgeKind::FallThrough => "",
+                EdgeKind::Taken => " [label=\"taken\"]",
+                EdgeKind::NotTaken => " [label=\"not taken\"]",
+                EdgeKind::Return => " [style=dashed, label=\"return\"]",
+            };
+            dot.push_str(&format!("    L{} -> L{}{};\n", edge.from, edge.to, style));
+        }
+        for (start, _) in self.blocks.iter().filter(|(_, b)| b.computed) {
+            dot.push_str(&format!("    L{} -> computed [style=dotted];\n", start));
+        }
+        dot.push_str("}\n");
+        dot
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::{Cfg, Edge, EdgeKind, SelfModification};
+    use crate::intcode_computer::assembler::assemble;
+
+    #[test]
+    fn cfg_blocks_and_analysis() {
+        let code = assemble(
+            "
+                    ARB #100
+                    ADD #ret, #0, [rb+0]
+                    JT #1, #func
+            ret:    INP [value]
+            loop:   JF [value], #end
+                    ADD [value], #-1, [value]
+                    ADD #5, #0, [patched]
+                    JT #1, #loop
+            end:    HLT
+            func:   OUT #1
+            patched: JF #0, [rb+0]
+            value:  DB 0, 0, 0
+            ",
+        )
+        .unwrap();
+        let cfg = Cfg::build(&code);
+        let starts: Vec<usize> = cfg.blocks().map(|b| b.start()).collect();
+        assert_eq!(starts, vec![0, 9, 11, 14, 25, 26]);
+        assert!(cfg.block(26).unwrap().computed);
+
+        let edge = |from, to, kind| Edge { from, to, kind };
+        assert_eq!(
+            cfg.edges(),
+            &[
+                edge(0, 26, EdgeKind::Jump),
+                edge(0, 9, EdgeKind::Return),
+                edge(9, 11, EdgeKind::FallThrough),
+                edge(11, 25, EdgeKind::Taken),
+                edge(11, 14, EdgeKind::NotTaken),
+                edge(14, 11, EdgeKind::Jump),
+            ][..]
+        );
+        assert_eq!(
+            cfg.self_modifications(),
+            &[SelfModification {
+                instruction: 18,
+                target: 28
+            }][..]
+        );
+        assert_eq!(cfg.unreachable().to_vec(), vec![31..34]);
+
+        let dot = cfg.to_dot();
+        assert!(dot.starts_with("digraph cfg {\n"));
+        assert!(dot.contains("    L11 -> L25 [label=\"taken\"];\n"));
+        assert!(dot.contains("    L26 -> computed [style=dotted];\n"));
+    }
+}
@@ -0,0 +1,135 @@
+use super::ports::Ports;
+use super::{Computer, ComputerError, RunState};
+use futures::{Sink, SinkExt, Stream, StreamExt};
+use std::thread::{self, JoinHandle};
+
+impl Computer {
+    /// Runs the computer on its own thread, until it halts, runs out of input or budget.
+    /// With a `Receiver` as input port, `Inp` instructions block until a value arrives,
+    /// and the computer only runs out of input once every sender is gone.
+    /// Its ports are dropped when it stops, closing an output channel for its receiver
+    pub fn spawn(mut self) -> JoinHandle<Result<Computer, ComputerError>> {
+        thread::spawn(move || {
+            let result = loop {
+                match self.execute() {
+                    Ok(RunState::ProducedOutput(value)) => self.emit(value),
+                    Ok(_) => break Ok(()),
+                    Err(e) => break Err(e),
+                }
+            };
+            self.ports = Ports::default();
+            result.map(|_| self)
+        })
+    }
+}
+
+/// Runs the computer as a task, awaiting its inputs from a stream and sending its
+/// outputs to a sink, until it halts, its budget is spent or the stream ends.
+/// The input port, if any, is read before awaiting the stream : a blocking port
+/// blocks the whole executor
+pub async fn run_async<I, O>(
+    mut computer: Computer,
+    mut input: I,
+    mut output: O,
+) -> Result<Computer, ComputerError>
+where
+    I: Stream<Item = i64> + Unpin,
+    O: Sink<i64> + Unpin,
+{
+    loop {
+        let state = computer.execute()?;
+        // Outputs sent once the sink is closed are dropped
+        let values: Vec<i64> = computer.output.drain(..).collect();
+        for value in values {
+            let _ = output.send(value).await;
+        }
+        match state {
+            RunState::ProducedOutput(value) => {
+                let _ = output.send(value).await;
+            }
+            RunState::NeedsInput => match input.next().await {
+                Some(value) => computer.input(value),
+                None => return Ok(computer),
+            },
+            _ => return Ok(computer),
+        }
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::run_async;
+    use crate::intcode_computer::fixtures::RUNNING_SUM;
+    use crate::intcode_computer::{parse_input, Computer};
+    use futures::channel::mpsc;
+    use futures::executor::LocalPool;
+    use futures::task::LocalSpawnExt;
+    use futures::StreamExt;
+    use std::sync::mpsc::channel;
+
+    /// Amplifier of the day 7 example, outputs 139629729 in a feedback loop with phases 9,8,7,6,5
+    const AMPLIFIER: &str =
+        "3,26,1001,26,-4,26,3,27,1002,27,2,27,1,27,26,27,4,27,1001,28,-1,28,1005,28,6,99,0,0,5";
+
+    #[test]
+    fn concurrent_threads() {
+        let code = parse_input(RUNNING_SUM);
+        let (input, receiver) = channel();
+        let (sender, output) = channel();
+        let handle = Computer::new(code)
+            .with_input_port(receiver)
+            .with_output_port(sender)
+            .spawn();
+
+        input.send(3).unwrap();
+        assert_eq!(output.recv(), Ok(3));
+        input.send(4).unwrap();
+        assert_eq!(output.recv(), Ok(7));
+        drop(input);
+        assert!(output.recv().is_err());
+        let computer = handle.join().unwrap().unwrap();
+        assert!(!computer.halted());
+    }
+
+    #[test]
+    fn concurrent_async_feedback_loop() {
+        let code = parse_input(AMPLIFIER);
+        let (senders, receivers): (Vec<_>, Vec<_>) =
+            (0..5).map(|_| mpsc::unbounded::<i64>()).unzip();
+        for (sender, phase) in senders.iter().zip(vec![9, 8, 7, 6, 5]) {
+            sender.unbounded_send(phase).unwrap();
+        }
+        senders[0].unbounded_send(0).unwrap();
+
+        // The last amplifier outputs to a relay, recording its signals for the thrusters
+        let (relay, mut relayed) = mpsc::unbounded();
+        let (thrusters, signals) = channel();
+        let to_first = senders[0].clone();
+        let mut pool = LocalPool::new();
+        pool.spawner()
+            .spawn_local(async move {
+                while let Some(signal) = relayed.next().await {
+                    thrusters.send(signal).unwrap();
+                    let _ = to_first.unbounded_send(signal);
+                }
+            })
+            .unwrap();
+        for (idx, receiver) in receivers.into_iter().enumerate() {
+            let next = if idx == 4 {
+                relay.clone()
+            } else {
+                senders[idx + 1].clone()
+            };
+            let task = run_async(Computer::new(code.clone()), receiver, next);
+            pool.spawner()
+                .spawn_local(async move {
+                    assert_eq!(task.await.map(|amp| amp.halted()), Ok(true));
+                })
+                .unwrap();
+        }
+        drop(relay);
+        drop(senders);
+        pool.run();
+        assert_eq!(signals.try_iter().last(), Some(139629729));
+    }
+}
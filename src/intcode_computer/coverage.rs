@@ -0,0 +1,271 @@
+use super::disassembler::{disassemble_with, Item};
+use super::instruction_set::InstructionSet;
+use super::{Computer, Instruction, Value};
+use std::collections::BTreeMap;
+use std::sync::Arc;
+
+/// Execution of an instruction, resolved before it runs
+pub(super) struct Hit {
+    pointer: usize,
+    instr: Instruction,
+    /// Whether a conditional jump is taken
+    taken: Option<bool>,
+}
+
+impl Hit {
+    pub(super) fn before(computer: &Computer, instr: &Instruction) -> Self {
+        let taken = match instr {
+            Instruction::JumpIfTrue(cond, _) if is_branch(instr) => {
+                computer.read_condition(cond).ok()
+            }
+            Instruction::JumpIfFalse(cond, _) if is_branch(instr) => {
+                computer.read_condition(cond).ok().map(|c| !c)
+            }
+            _ => None,
+        };
+        Hit {
+            pointer: computer.pointer,
+            instr: instr.clone(),
+            taken,
+        }
+    }
+}
+
+/// Tells whether an instruction is a conditional jump, with a condition known at runtime
+fn is_branch(instr: &Instruction) -> bool {
+    match instr {
+        Instruction::JumpIfTrue(cond, _) | Instruction::JumpIfFalse(cond, _) => {
+            !matches!(cond, Value::Immediate(_))
+        }
+        _ => false,
+    }
+}
+
+/// Number of times each direction of a conditional jump was followed
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Branch {
+    pub taken: u64,
+    pub not_taken: u64,
+}
+
+impl Branch {
+    /// Number of directions followed at least once
+    fn covered(&self) -> usize {
+        (self.taken > 0) as usize + (self.not_taken > 0) as usize
+    }
+}
+
+/// Instruction of a coverage report
+struct Line {
+    addr: usize,
+    instr: Instruction,
+    count: u64,
+    /// Directions followed, for the conditional jumps
+    branch: Option<Branch>,
+}
+
+/// Totals of a coverage report
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Summary {
+    /// Instructions of the program, statically reachable or executed
+    pub instructions: usize,
+    pub executed: usize,
+    /// Directions of the conditional jumps, two per jump
+    pub branches: usize,
+    pub covered_branches: usize,
+}
+
+impl std::fmt::Display for Summary {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        writeln!(
+            f,
+            "instructions: {}/{} executed",
+            self.executed, self.instructions
+        )?;
+        writeln!(
+            f,
+            "branches:     {}/{} directions taken",
+            self.covered_branches, self.branches
+        )
+    }
+}
+
+/// Instructions executed by a computer, and directions followed by its conditional jumps
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Coverage {
+    /// Executed instructions, by address, as last decoded there
+    pub instructions: BTreeMap<usize, Instruction>,
+    /// Number of executions, per address
+    pub executions: BTreeMap<usize, u64>,
+    /// Conditional jumps executed, per address
+    pub branches: BTreeMap<usize, Branch>,
+    /// Opcodes of the computer, to disassemble the program
+    pub(super) instruction_set: Option<Arc<InstructionSet>>,
+}
+
+impl Coverage {
+    pub(super) fn record(&mut self, hit: Hit) {
+        *self.executions.entry(hit.pointer).or_insert(0) += 1;
+        if let Some(taken) = hit.taken {
+            let branch = self.branches.entry(hit.pointer).or_default();
+            match taken {
+                true => branch.taken += 1,
+                false => branch.not_taken += 1,
+            }
+        }
+        self.instructions.insert(hit.pointer, hit.instr);
+    }
+
+    /// Adds up the coverage of another run, to aggregate several inputs
+    pub fn merge(&mut self, other: &Coverage) {
+        for (addr, count) in other.executions.iter() {
+            *self.executions.entry(*addr).or_insert(0) += count;
+        }
+        for (addr, branch) in other.branches.iter() {
+            let into = self.branches.entry(*addr).or_default();
+            into.taken += branch.taken;
+            into.not_taken += branch.not_taken;
+        }
+        for (addr, instr) in other.instructions.iter() {
+            self.instructions.insert(*addr, instr.clone());
+        }
+        if self.instruction_set.is_none() {
+            self.instruction_set = other.instruction_set.clone();
+        }
+    }
+
+    /// Instructions of the program: the ones reachable in the disassembly of its image,
+    /// and the ones executed elsewhere, such as self-modified code
+    fn lines(&self, code: &[i64]) -> Vec<Line> {
+        let standard = InstructionSet::standard();
+        let set = self.instruction_set.as_deref().unwrap_or(&standard);
+        let mut instructions: BTreeMap<usize, Instruction> = disassemble_with(code, set)
+            .items()
+            .filter_map(|(addr, item)| match item {
+                Item::Code(instr) => Some((*addr, instr.clone())),
+                Item::Data(_) => None,
+            })
+            .collect();
+        for (addr, instr) in self.instructions.iter() {
+            instructions.insert(*addr, instr.clone());
+        }
+        instructions
+            .into_iter()
+            .map(|(addr, instr)| Line {
+                addr,
+                count: self.executions.get(&addr).cloned().unwrap_or(0),
+                branch: match self.branches.get(&addr) {
+                    Some(branch) => Some(*branch),
+                    None if is_branch(&instr) => Some(Branch::default()),
+                    None => None,
+                },
+                instr,
+            })
+            .collect()
+    }
+
+    pub fn summary(&self, code: &[i64]) -> Summary {
+        let lines = self.lines(code);
+        let branches: Vec<Branch> = lines.iter().filter_map(|line| line.branch).collect();
+        Summary {
+            instructions: lines.len(),
+            executed: lines.iter().filter(|line| line.count > 0).count(),
+            branches: 2 * branches.len(),
+            covered_branches: branches.iter().map(|b| b.covered()).sum(),
+        }
+    }
+
+    /// Disassembly of the program, with the execution count of each instruction and the
+    /// directions followed by each conditional jump. Instructions never executed are marked
+    /// with `#####`
+    pub fn annotate(&self, code: &[i64]) -> String {
+        let mut report = String::new();
+        for line in self.lines(code) {
+            let count = match line.count {
+                0 => "#####".to_string(),
+                count => count.to_string(),
+            };
+            let mut text = format!("{:>10} | {:>5}: {}", count, line.addr, line.instr);
+            if let Some(branch) = line.branch {
+                text = format!(
+                    "{:<50} taken {}, not taken {}",
+                    text, branch.taken, branch.not_taken
+                );
+            }
+            report.push_str(&text);
+            report.push('\n');
+        }
+        report.push_str(&self.summary(code).to_string());
+        report
+    }
+
+    /// Exports the coverage in the lcov tracefile format, for the source file `name`.
+    /// Lines are the addresses of the instructions, plus one as lcov lines start at 1
+    pub fn to_lcov(&self, code: &[i64], name: &str) -> String {
+        let lines = self.lines(code);
+        let mut lcov = format!("TN:\nSF:{}\n", name);
+        for line in lines.iter() {
+            if let Some(branch) = line.branch {
+                for (idx, count) in [branch.taken, branch.not_taken].iter().enumerate() {
+                    let count = match line.count {
+                        0 => "-".to_string(),
+                        _ => count.to_string(),
+                    };
+                    lcov.push_str(&format!("BRDA:{},0,{},{}\n", line.addr + 1, idx, count));
+                }
+            }
+        }
+        let summary = self.summary(code);
+        lcov.push_str(&format!("BRF:{}\n", summary.branches));
+        lcov.push_str(&format!("BRH:{}\n", summary.covered_branches));
+        for line in lines.iter() {
+            lcov.push_str(&format!("DA:{},{}\n", line.addr + 1, line.count));
+        }
+        lcov.push_str(&format!("LF:{}\n", summary.instructions));
+        lcov.push_str(&format!("LH:{}\n", summary.executed));
+        lcov.push_str("end_of_record\n");
+        lcov
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::Summary;
+    use crate::intcode_computer::{parse_input, Computer};
+
+    #[test]
+    fn coverage_of_branches() {
+        // Outputs 0 if the input is 0, 1 otherwise. The jump target is read from memory
+        let code = parse_input("3,12,6,12,15,1,13,14,13,4,13,99,-1,0,1,9");
+        let run = |input| {
+            let mut computer = Computer::new(code.clone()).record_coverage();
+            computer.input(input);
+            computer.execute().unwrap();
+            computer.take_coverage().unwrap()
+        };
+
+        let mut coverage = run(0);
+        assert_eq!(
+            coverage.summary(&code),
+            Summary {
+                instructions: 5,
+                executed: 4,
+                branches: 2,
+                covered_branches: 1,
+            }
+        );
+        let report = coverage.annotate(&code);
+        assert!(report.contains("     ##### |     5: ADD [13], [14], [13]\n"));
+        assert!(report.contains("taken 1, not taken 0\n"));
+
+        coverage.merge(&run(5));
+        assert_eq!(coverage.summary(&code).executed, 5);
+        assert_eq!(coverage.summary(&code).covered_branches, 2);
+        assert_eq!(coverage.executions[&0], 2);
+
+        let lcov = coverage.to_lcov(&code, "jump.intcode");
+        assert!(lcov.starts_with("TN:\nSF:jump.intcode\nBRDA:3,0,0,1\nBRDA:3,0,1,1\n"));
+        assert!(lcov.contains("DA:6,1\n"));
+        assert!(lcov.ends_with("LF:5\nLH:5\nend_of_record\n"));
+    }
+}
@@ -0,0 +1,168 @@
+use super::{Computer, ComputerError, Instruction, RunState};
+use std::collections::BTreeSet;
+
+/// Reason why the debugger handed control back
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Stop {
+    /// The code pointer reached a breakpoint, the instruction there is not executed yet
+    Breakpoint(usize),
+    /// A watched memory cell changed value
+    Watchpoint { address: usize, old: i64, new: i64 },
+    /// The computer itself stopped
+    Computer(RunState),
+}
+
+/// Wraps a computer to execute it under control of breakpoints and watchpoints
+#[derive(Debug)]
+pub struct Debugger {
+    /// The computer being debugged
+    computer: Computer,
+    /// Addresses of the instructions to stop at
+    breakpoints: BTreeSet<usize>,
+    /// Addresses of the memory cells to watch
+    watchpoints: BTreeSet<usize>,
+}
+
+impl Debugger {
+    /// Creates a debugger for the given computer
+    pub fn new(computer: Computer) -> Self {
+        Debugger {
+            computer,
+            breakpoints: BTreeSet::new(),
+            watchpoints: BTreeSet::new(),
+        }
+    }
+
+    /// The computer being debugged
+    pub fn computer(&self) -> &Computer {
+        &self.computer
+    }
+
+    /// The computer being debugged, to feed input or edit memory
+    pub fn computer_mut(&mut self) -> &mut Computer {
+        &mut self.computer
+    }
+
+    /// Gives back the debugged computer
+    pub fn into_computer(self) -> Computer {
+        self.computer
+    }
+
+    /// Stops the execution before the instruction at the given address.
+    /// Returns false if there already was a breakpoint there
+    pub fn add_breakpoint(&mut self, addr: usize) -> bool {
+        self.breakpoints.insert(addr)
+    }
+
+    /// Removes a breakpoint. Returns false if there was none at the given address
+    pub fn remove_breakpoint(&mut self, addr: usize) -> bool {
+        self.breakpoints.remove(&addr)
+    }
+
+    pub fn breakpoints(&self) -> impl Iterator<Item = &usize> {
+        self.breakpoints.iter()
+    }
+
+    /// Stops the execution whenever the memory cell at the given address changes.
+    /// Returns false if the cell was already watched
+    pub fn add_watchpoint(&mut self, addr: usize) -> bool {
+        self.watchpoints.insert(addr)
+    }
+
+    /// Removes a watchpoint. Returns false if the cell was not watched
+    pub fn remove_watchpoint(&mut self, addr: usize) -> bool {
+        self.watchpoints.remove(&addr)
+    }
+
+    pub fn watchpoints(&self) -> impl Iterator<Item = &usize> {
+        self.watchpoints.iter()
+    }
+
+    /// Decodes the instruction that will be executed next
+    pub fn current_instruction(&self) -> Result<Instruction, ComputerError> {
+        self.computer.current_instruction()
+    }
+
+    /// Executes a single instruction, ignoring breakpoints
+    pub fn step(&mut self) -> Result<Option<Stop>, ComputerError> {
+        let watched: Vec<(usize, i64)> = self
+            .watchpoints
+            .iter()
+            .map(|addr| (*addr, self.computer.get(*addr)))
+            .collect();
+
+        let state = self.computer.step()?;
+
+        let changed = watched.into_iter().find_map(|(address, old)| {
+            let new = self.computer.get(address);
+            if new != old {
+                Some(Stop::Watchpoint { address, old, new })
+            } else {
+                None
+            }
+        });
+        Ok(changed.or_else(|| state.map(Stop::Computer)))
+    }
+
+    /// Executes until a breakpoint or a watchpoint is hit, or the computer stops.
+    /// A breakpoint on the current instruction does not stop the execution
+    pub fn resume(&mut self) -> Result<Stop, ComputerError> {
+        loop {
+            if let Some(stop) = self.step()? {
+                return Ok(stop);
+            }
+            let pointer = self.computer.pointer();
+            if self.breakpoints.contains(&pointer) {
+                return Ok(Stop::Breakpoint(pointer));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::{Debugger, Stop};
+    use crate::intcode_computer::assembler::assemble;
+    use crate::intcode_computer::fixtures::COUNTDOWN;
+    use crate::intcode_computer::{Computer, Instruction, RunState, Value};
+
+    #[test]
+    fn debugger_breakpoints_and_watchpoints() {
+        let code = assemble(COUNTDOWN).unwrap();
+        let mut debugger = Debugger::new(Computer::new(code));
+        debugger.add_breakpoint(4);
+        debugger.add_watchpoint(14);
+
+        assert_eq!(
+            debugger.resume(),
+            Ok(Stop::Watchpoint {
+                address: 14,
+                old: 0,
+                new: 2
+            })
+        );
+        assert_eq!(
+            debugger.current_instruction(),
+            Ok(Instruction::Out(Value::Position(14)))
+        );
+        assert_eq!(debugger.step(), Ok(None));
+        assert_eq!(debugger.computer().output, vec![2]);
+        assert_eq!(
+            debugger.resume(),
+            Ok(Stop::Watchpoint {
+                address: 14,
+                old: 2,
+                new: 1
+            })
+        );
+
+        assert!(debugger.remove_watchpoint(14));
+        assert_eq!(debugger.resume(), Ok(Stop::Breakpoint(4)));
+
+        // Edit the memory before continuing
+        debugger.computer_mut().set(14, 3);
+        assert!(debugger.remove_breakpoint(4));
+        assert_eq!(debugger.resume(), Ok(Stop::Computer(RunState::Halted)));
+        assert_eq!(debugger.computer().output, vec![2, 3, 2, 1]);
+    }
+}
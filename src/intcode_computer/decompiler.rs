@@ -0,0 +1,1536 @@
+use super::ir::{BinOp, Block, IrProgram, Op, Terminator};
+use super::Value;
+use std::collections::{BTreeMap, BTreeSet};
+
+/// Expression of the pseudo-code
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Expr {
+    Const(i64),
+    Var(String),
+    Input,
+    Binary(BinOp, Box<Expr>, Box<Expr>),
+    Not(Box<Expr>),
+}
+
+impl Expr {
+    /// Builds an operation, folding its constant operands
+    fn binary(op: BinOp, a: Expr, b: Expr) -> Expr {
+        if let (Expr::Const(x), Expr::Const(y)) = (&a, &b) {
+            if let Some(value) = op.apply(*x, *y) {
+                return Expr::Const(value);
+            }
+        }
+        Expr::Binary(op, Box::new(a), Box::new(b))
+    }
+
+    fn not(self) -> Expr {
+        match self {
+            Expr::Not(expr) => *expr,
+            Expr::Const(value) => Expr::Const((value == 0) as i64),
+            expr => Expr::Not(Box::new(expr)),
+        }
+    }
+
+    /// Calls `f` on each variable read by the expression
+    fn visit<F: FnMut(&str)>(&self, f: &mut F) {
+        match self {
+            Expr::Var(name) => f(name),
+            Expr::Binary(_, a, b) => {
+                a.visit(f);
+                b.visit(f);
+            }
+            Expr::Not(expr) => expr.visit(f),
+            Expr::Const(_) | Expr::Input => (),
+        }
+    }
+
+    fn reads(&self, name: &str) -> bool {
+        let mut found = false;
+        self.visit(&mut |var| found |= var == name);
+        found
+    }
+
+    fn reads_input(&self) -> bool {
+        match self {
+            Expr::Input => true,
+            Expr::Binary(_, a, b) => a.reads_input() || b.reads_input(),
+            Expr::Not(expr) => expr.reads_input(),
+            Expr::Const(_) | Expr::Var(_) => false,
+        }
+    }
+
+    /// Replaces a variable by an expression. Returns whether the variable was found
+    fn substitute(&mut self, name: &str, expr: &Expr) -> bool {
+        match self {
+            Expr::Var(var) if var == name => {
+                *self = expr.clone();
+                true
+            }
+            Expr::Binary(_, a, b) => a.substitute(name, expr) | b.substitute(name, expr),
+            Expr::Not(inner) => inner.substitute(name, expr),
+            _ => false,
+        }
+    }
+
+    /// Folds the constants, and rewrites sums and products by constants
+    /// as `c1 * x + c2 * y + c0`
+    fn simplify(self) -> Expr {
+        let expr = match self {
+            Expr::Binary(op, a, b) => Expr::binary(op, a.simplify(), b.simplify()),
+            Expr::Not(expr) => expr.simplify().not(),
+            expr => expr,
+        };
+        match expr {
+            Expr::Binary(BinOp::Add, _, _) | Expr::Binary(BinOp::Mul, _, _) => {
+                expr.affine().map(Expr::from_affine).unwrap_or(expr)
+            }
+            expr => expr,
+        }
+    }
+
+    /// Constant term and coefficients of the variables, if the expression is affine
+    fn affine(&self) -> Option<(i64, BTreeMap<String, i64>)> {
+        match self {
+            Expr::Const(value) => Some((*value, BTreeMap::new())),
+            Expr::Var(name) => Some((0, vec![(name.clone(), 1)].into_iter().collect())),
+            Expr::Binary(BinOp::Add, a, b) => {
+                let (ca, mut terms) = a.affine()?;
+                let (cb, others) = b.affine()?;
+                for (name, k) in others {
+                    let coef = terms.entry(name).or_insert(0);
+                    *coef = coef.checked_add(k)?;
+                }
+                terms.retain(|_, k| *k != 0);
+                Some((ca.checked_add(cb)?, terms))
+            }
+            Expr::Binary(BinOp::Mul, a, b) => {
+                let (ca, ta) = a.affine()?;
+                let (cb, tb) = b.affine()?;
+                let (constant, terms, factor) = match (ta.is_empty(), tb.is_empty()) {
+                    (_, true) => (ca, ta, cb),
+                    (true, false) => (cb, tb, ca),
+                    (false, false) => return None,
+                };
+                let mut scaled = BTreeMap::new();
+                for (name, k) in terms {
+                    scaled.insert(name, k.checked_mul(factor)?);
+                }
+                scaled.retain(|_, k| *k != 0);
+                Some((constant.checked_mul(factor)?, scaled))
+            }
+            _ => None,
+        }
+    }
+
+    pub(super) fn from_affine((constant, terms): (i64, BTreeMap<String, i64>)) -> Expr {
+        let mut sum: Option<Expr> = None;
+        for (name, k) in terms {
+            let term = match k {
+                1 => Expr::Var(name),
+                k => Expr::Binary(
+                    BinOp::Mul,
+                    Box::new(Expr::Const(k)),
+                    Box::new(Expr::Var(name)),
+                ),
+            };
+            sum = Some(match sum {
+                Some(sum) => Expr::Binary(BinOp::Add, Box::new(sum), Box::new(term)),
+                None => term,
+            });
+        }
+        match sum {
+            Some(sum) if constant == 0 => sum,
+            Some(sum) => Expr::Binary(BinOp::Add, Box::new(sum), Box::new(Expr::Const(constant))),
+            None => Expr::Const(constant),
+        }
+    }
+
+    fn precedence(&self) -> u8 {
+        let op = match self {
+            Expr::Binary(op, _, _) => op,
+            Expr::Not(expr) => match &**expr {
+                Expr::Binary(op @ BinOp::Equals, _, _)
+                | Expr::Binary(op @ BinOp::LessThan, _, _) => op,
+                _ => return 5,
+            },
+            _ => return 5,
+        };
+        match op {
+            BinOp::Equals => 1,
+            BinOp::LessThan => 2,
+            BinOp::Add => 3,
+            BinOp::Mul => 4,
+        }
+    }
+
+    /// Writes the expression as the operand of an operation of the given precedence
+    fn fmt_operand(&self, f: &mut std::fmt::Formatter, min: u8) -> std::fmt::Result {
+        if self.precedence() < min {
+            write!(f, "({})", self)
+        } else {
+            write!(f, "{}", self)
+        }
+    }
+
+    fn fmt_binary(
+        &self,
+        f: &mut std::fmt::Formatter,
+        symbol: &str,
+        a: &Expr,
+        b: &Expr,
+    ) -> std::fmt::Result {
+        let precedence = self.precedence();
+        a.fmt_operand(f, precedence)?;
+        write!(f, " {} ", symbol)?;
+        // Sums and products are associative, comparisons are not
+        let associative = symbol == "+" || symbol == "*";
+        b.fmt_operand(f, precedence + !associative as u8)
+    }
+}
+
+impl std::fmt::Display for Expr {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Expr::Const(value) => write!(f, "{}", value),
+            Expr::Var(name) => write!(f, "{}", name),
+            Expr::Input => write!(f, "input()"),
+            Expr::Binary(BinOp::Add, a, b) => match **b {
+                Expr::Const(value) if value < 0 && value != i64::MIN => {
+                    a.fmt_operand(f, 3)?;
+                    write!(f, " - {}", -value)
+                }
+                _ => self.fmt_binary(f, "+", a, b),
+            },
+            Expr::Binary(op, a, b) => self.fmt_binary(f, op.symbol(), a, b),
+            Expr::Not(expr) => match &**expr {
+                Expr::Binary(BinOp::Equals, a, b) => self.fmt_binary(f, "!=", a, b),
+                Expr::Binary(BinOp::LessThan, a, b) => self.fmt_binary(f, ">=", a, b),
+                expr => {
+                    write!(f, "!")?;
+                    expr.fmt_operand(f, 5)
+                }
+            },
+        }
+    }
+}
+
+/// Statement of the pseudo-code
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Stmt {
+    Assign(String, Expr),
+    Output(Expr),
+    /// Change of the relative base that could not be followed statically
+    AdjustBase(Expr),
+    Call {
+        function: usize,
+        args: Vec<Expr>,
+    },
+    If {
+        cond: Expr,
+        then: Vec<Stmt>,
+        otherwise: Vec<Stmt>,
+    },
+    While {
+        cond: Expr,
+        body: Vec<Stmt>,
+    },
+    DoWhile {
+        body: Vec<Stmt>,
+        cond: Expr,
+    },
+    Break,
+    Continue,
+    Return,
+    Halt,
+    Goto(usize),
+    /// Jump to an address only known at runtime
+    GotoComputed(Expr),
+    Label(usize),
+    /// Instruction that cannot be decoded
+    Invalid(usize),
+}
+
+impl Stmt {
+    /// Calls `f` on each variable read by the statement
+    fn visit<F: FnMut(&str)>(&self, f: &mut F) {
+        match self {
+            Stmt::Assign(_, expr)
+            | Stmt::Output(expr)
+            | Stmt::AdjustBase(expr)
+            | Stmt::GotoComputed(expr) => expr.visit(f),
+            Stmt::Call { args, .. } => args.iter().for_each(|arg| arg.visit(f)),
+            Stmt::If {
+                cond,
+                then,
+                otherwise,
+            } => {
+                cond.visit(f);
+                then.iter().chain(otherwise.iter()).for_each(|s| s.visit(f));
+            }
+            Stmt::While { cond, body } | Stmt::DoWhile { body, cond } => {
+                cond.visit(f);
+                body.iter().for_each(|s| s.visit(f));
+            }
+            _ => (),
+        }
+    }
+
+    fn reads(&self, name: &str) -> bool {
+        let mut found = false;
+        self.visit(&mut |var| found |= var == name);
+        found
+    }
+
+    fn writes(&self) -> Option<&String> {
+        match self {
+            Stmt::Assign(name, _) => Some(name),
+            _ => None,
+        }
+    }
+
+    /// Replaces a variable by an expression, wherever the statement reads it.
+    /// Returns whether the variable was found
+    fn substitute(&mut self, name: &str, expr: &Expr) -> bool {
+        let all = |stmts: &mut Vec<Stmt>| {
+            stmts
+                .iter_mut()
+                .fold(false, |found, s| s.substitute(name, expr) | found)
+        };
+        match self {
+            Stmt::Assign(_, e) | Stmt::Output(e) | Stmt::AdjustBase(e) | Stmt::GotoComputed(e) => {
+                e.substitute(name, expr)
+            }
+            Stmt::Call { args, .. } => args
+                .iter_mut()
+                .fold(false, |found, arg| arg.substitute(name, expr) | found),
+            Stmt::If {
+                cond,
+                then,
+                otherwise,
+            } => cond.substitute(name, expr) | all(then) | all(otherwise),
+            Stmt::While { cond, body } | Stmt::DoWhile { body, cond } => {
+                cond.substitute(name, expr) | all(body)
+            }
+            _ => false,
+        }
+    }
+
+    /// Applies `f` to each expression of the statement
+    fn map_exprs<F: Fn(Expr) -> Expr>(self, f: &F) -> Stmt {
+        let all = |stmts: Vec<Stmt>| stmts.into_iter().map(|s| s.map_exprs(f)).collect();
+        match self {
+            Stmt::Assign(name, expr) => Stmt::Assign(name, f(expr)),
+            Stmt::Output(expr) => Stmt::Output(f(expr)),
+            Stmt::AdjustBase(expr) => Stmt::AdjustBase(f(expr)),
+            Stmt::GotoComputed(expr) => Stmt::GotoComputed(f(expr)),
+            Stmt::Call { function, args } => Stmt::Call {
+                function,
+                args: args.into_iter().map(f).collect(),
+            },
+            Stmt::If {
+                cond,
+                then,
+                otherwise,
+            } => Stmt::If {
+                cond: f(cond),
+                then: all(then),
+                otherwise: all(otherwise),
+            },
+            Stmt::While { cond, body } => Stmt::While {
+                cond: f(cond),
+                body: all(body),
+            },
+            Stmt::DoWhile { body, cond } => Stmt::DoWhile {
+                body: all(body),
+                cond: f(cond),
+            },
+            stmt => stmt,
+        }
+    }
+
+    /// Whether the execution never continues with the next statement
+    fn is_jump(&self) -> bool {
+        matches!(
+            self,
+            Stmt::Break
+                | Stmt::Continue
+                | Stmt::Return
+                | Stmt::Halt
+                | Stmt::Goto(_)
+                | Stmt::GotoComputed(_)
+                | Stmt::Invalid(_)
+        )
+    }
+
+    fn fmt_indented(&self, f: &mut std::fmt::Formatter, indent: usize) -> std::fmt::Result {
+        let pad = "    ".repeat(indent);
+        let block = |f: &mut std::fmt::Formatter, stmts: &[Stmt]| {
+            stmts.iter().try_for_each(|s| s.fmt_indented(f, indent + 1))
+        };
+        match self {
+            Stmt::Assign(name, expr) => writeln!(f, "{}{} = {};", pad, name, expr),
+            Stmt::Output(expr) => writeln!(f, "{}output({});", pad, expr),
+            Stmt::AdjustBase(expr) => writeln!(f, "{}rb += {};", pad, expr),
+            Stmt::Call { function, args } => {
+                let args: Vec<String> = args.iter().map(|arg| arg.to_string()).collect();
+                writeln!(f, "{}f{}({});", pad, function, args.join(", "))
+            }
+            Stmt::If {
+                cond,
+                then,
+                otherwise,
+            } => {
+                writeln!(f, "{}if ({}) {{", pad, cond)?;
+                block(f, then)?;
+                if !otherwise.is_empty() {
+                    writeln!(f, "{}}} else {{", pad)?;
+                    block(f, otherwise)?;
+                }
+                writeln!(f, "{}}}", pad)
+            }
+            Stmt::While { cond, body } => {
+                writeln!(f, "{}while ({}) {{", pad, cond)?;
+                block(f, body)?;
+                writeln!(f, "{}}}", pad)
+            }
+            Stmt::DoWhile { body, cond } => {
+                writeln!(f, "{}do {{", pad)?;
+                block(f, body)?;
+                writeln!(f, "{}}} while ({});", pad, cond)
+            }
+            Stmt::Break => writeln!(f, "{}break;", pad),
+            Stmt::Continue => writeln!(f, "{}continue;", pad),
+            Stmt::Return => writeln!(f, "{}return;", pad),
+            Stmt::Halt => writeln!(f, "{}halt();", pad),
+            Stmt::Goto(addr) => writeln!(f, "{}goto L{};", pad, addr),
+            Stmt::GotoComputed(expr) => writeln!(f, "{}goto *{};", pad, expr),
+            Stmt::Label(addr) => writeln!(f, "{}L{}:", pad, addr),
+            Stmt::Invalid(addr) => writeln!(f, "{}/* invalid instruction at {} */", pad, addr),
+        }
+    }
+}
+
+/// Decompiled function. Its frame starts at the relative base of the caller, with the
+/// return address in slot 0, followed by the parameters `p1, p2...`, the locals `v3...`,
+/// and the outgoing slots `out0, out1...` used for calls. Parameters written by the function
+/// are in/out slots, declared by reference: the caller can read them after the call
+#[derive(Debug, Clone)]
+pub struct Function {
+    pub entry: usize,
+    pub params: usize,
+    pub body: Vec<Stmt>,
+}
+
+/// Whether one of the statements assigns the variable
+fn assigns(stmts: &[Stmt], name: &str) -> bool {
+    stmts.iter().any(|stmt| match stmt {
+        Stmt::Assign(written, _) => written == name,
+        Stmt::If {
+            then, otherwise, ..
+        } => assigns(then, name) || assigns(otherwise, name),
+        Stmt::While { body, .. } | Stmt::DoWhile { body, .. } => assigns(body, name),
+        _ => false,
+    })
+}
+
+impl Function {
+    /// Whether the function writes its nth parameter, starting at 1
+    pub fn writes_param(&self, n: usize) -> bool {
+        assigns(&self.body, &format!("p{}", n))
+    }
+}
+
+impl std::fmt::Display for Function {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        if self.entry == 0 {
+            writeln!(f, "void main() {{")?;
+        } else {
+            let params: Vec<String> = (1..=self.params)
+                .map(|p| match self.writes_param(p) {
+                    true => format!("int &p{}", p),
+                    false => format!("int p{}", p),
+                })
+                .collect();
+            writeln!(f, "void f{}({}) {{", self.entry, params.join(", "))?;
+        }
+        for stmt in self.body.iter() {
+            stmt.fmt_indented(f, 1)?;
+        }
+        writeln!(f, "}}")
+    }
+}
+
+/// Pseudo-C of a program
+#[derive(Debug, Clone)]
+pub struct Decompiled {
+    functions: Vec<Function>,
+}
+
+impl Decompiled {
+    /// Functions by entry address, starting with `main` at address 0
+    pub fn functions(&self) -> &[Function] {
+        &self.functions
+    }
+
+    pub fn function(&self, entry: usize) -> Option<&Function> {
+        self.functions
+            .iter()
+            .find(|function| function.entry == entry)
+    }
+}
+
+impl std::fmt::Display for Decompiled {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        for (idx, function) in self.functions.iter().enumerate() {
+            if idx > 0 {
+                writeln!(f)?;
+            }
+            write!(f, "{}", function)?;
+        }
+        Ok(())
+    }
+}
+
+/// How a translated block ends
+#[derive(Debug, Clone)]
+enum Exit {
+    Goto(usize),
+    /// Jumps to `target` when `cond` holds, else continues at `next`
+    Branch {
+        cond: Expr,
+        target: usize,
+        next: usize,
+    },
+    End(Stmt),
+}
+
+impl Exit {
+    fn successors(&self) -> Vec<usize> {
+        match self {
+            Exit::Goto(target) => vec![*target],
+            Exit::Branch { target, next, .. } => vec![*next, *target],
+            Exit::End(_) => vec![],
+        }
+    }
+
+    fn reads(&self, name: &str) -> bool {
+        match self {
+            Exit::Goto(_) => false,
+            Exit::Branch { cond, .. } => cond.reads(name),
+            Exit::End(stmt) => stmt.reads(name),
+        }
+    }
+
+    fn substitute(&mut self, name: &str, expr: &Expr) -> bool {
+        match self {
+            Exit::Goto(_) => false,
+            Exit::Branch { cond, .. } => cond.substitute(name, expr),
+            Exit::End(stmt) => stmt.substitute(name, expr),
+        }
+    }
+}
+
+/// Entry addresses of the blocks that can follow a block, within its function
+fn successors(block: &Block) -> Vec<usize> {
+    match &block.terminator {
+        Terminator::Jump(target) => vec![*target],
+        Terminator::Call { returns_to, .. } => vec![*returns_to],
+        Terminator::Branch {
+            target: Value::Immediate(t),
+            next,
+            ..
+        } if *t >= 0 => vec![*t as usize, *next],
+        Terminator::Branch { next, .. } => vec![*next],
+        _ => vec![],
+    }
+}
+
+/// Relative base before each operation of a block, and after the block,
+/// from the one at the entry of the function
+fn op_bases(block: &Block, base: Option<i64>) -> (Vec<Option<i64>>, Option<i64>) {
+    let mut base = base;
+    let mut bases = vec![];
+    for (_, op) in block.ops.iter() {
+        bases.push(base);
+        if let Op::AdjustBase { delta } = op {
+            base = match (base, delta) {
+                (Some(base), Value::Immediate(delta)) => base.checked_add(*delta),
+                _ => None,
+            };
+        }
+    }
+    (bases, base)
+}
+
+fn destination(op: &Op) -> Option<&Value> {
+    match op {
+        Op::Copy { dst, .. } | Op::Binary { dst, .. } | Op::Input { dst } => Some(dst),
+        Op::Output { .. } | Op::AdjustBase { .. } => None,
+    }
+}
+
+/// Blocks of the function at the given entry, with the relative base at their entry.
+/// Called functions are assumed to restore the relative base before returning
+fn relative_bases(blocks: &BTreeMap<usize, &Block>, entry: usize) -> BTreeMap<usize, Option<i64>> {
+    let mut bases: BTreeMap<usize, Option<i64>> = BTreeMap::new();
+    let mut todo = vec![(entry, Some(0))];
+    while let Some((addr, base)) = todo.pop() {
+        let block = match blocks.get(&addr) {
+            Some(block) => block,
+            None => continue,
+        };
+        let base = match bases.get(&addr) {
+            None => base,
+            Some(known) if *known == base || known.is_none() => continue,
+            // Reached with different bases
+            Some(_) => None,
+        };
+        bases.insert(addr, base);
+        let (_, end) = op_bases(block, base);
+        todo.extend(successors(block).into_iter().map(|next| (next, end)));
+    }
+    bases
+}
+
+/// Whether an assignment can be moved after the given statements
+fn can_move(name: &str, expr: &Expr, over: &[Stmt]) -> bool {
+    if expr.reads_input() && !over.is_empty() {
+        return false;
+    }
+    let mut vars = vec![name.to_string()];
+    expr.visit(&mut |var| vars.push(var.to_string()));
+    over.iter().all(|stmt| {
+        let barrier = matches!(stmt, Stmt::Call { .. } | Stmt::AdjustBase(_));
+        let clobbers = stmt.writes().is_some_and(|written| vars.contains(written));
+        !barrier && !clobbers && !stmt.reads(name)
+    })
+}
+
+/// Folds the assignments of temporaries, written and read once, into the statement reading them
+fn fold<F: Fn(&str) -> bool>(stmts: &mut Vec<Stmt>, exit: &mut Exit, temporary: F) {
+    let mut idx = 0;
+    while idx < stmts.len() {
+        let candidate = match &stmts[idx] {
+            Stmt::Assign(name, expr) if temporary(name) => {
+                let reader = stmts[idx + 1..]
+                    .iter()
+                    .position(|s| s.reads(name))
+                    .map(|r| idx + 1 + r);
+                let end = reader.unwrap_or(stmts.len());
+                let read = reader.is_some() || exit.reads(name);
+                if read && can_move(name, expr, &stmts[idx + 1..end]) {
+                    Some((reader, name.clone(), expr.clone()))
+                } else {
+                    None
+                }
+            }
+            _ => None,
+        };
+        let (reader, name, expr) = match candidate {
+            Some(candidate) => candidate,
+            None => {
+                idx += 1;
+                continue;
+            }
+        };
+        let substituted = match reader {
+            Some(reader) => stmts[reader].substitute(&name, &expr),
+            None => exit.substitute(&name, &expr),
+        };
+        // The temporary stays in place when its reader could not take the expression
+        if substituted {
+            stmts.remove(idx);
+        } else {
+            idx += 1;
+        }
+    }
+}
+
+/// Relative base frame of a function
+struct Frame {
+    main: bool,
+    params: i64,
+    /// Number of slots of the frame, outgoing slots excluded
+    size: i64,
+}
+
+/// Decompiler of Intcode programs into pseudo-C, built on their basic blocks.
+/// The code is decompiled as it is in the image: writes over the code are not followed
+#[derive(Debug, Clone)]
+pub struct Decompiler {
+    code: Vec<i64>,
+    names: BTreeMap<usize, String>,
+    constant_memory: bool,
+}
+
+impl Decompiler {
+    pub fn new(code: &[i64]) -> Self {
+        Decompiler {
+            code: code.to_vec(),
+            names: BTreeMap::new(),
+            constant_memory: false,
+        }
+    }
+
+    /// Names a memory cell, written `m<addr>` otherwise. A named cell is never read as a constant
+    pub fn name(mut self, addr: usize, name: &str) -> Self {
+        self.names.insert(addr, name.to_string());
+        self
+    }
+
+    /// Reads the cells the program never writes as constants, assuming that
+    /// the frames of the functions stay on the stack
+    pub fn constant_memory(mut self) -> Self {
+        self.constant_memory = true;
+        self
+    }
+
+    pub fn decompile(&self) -> Decompiled {
+        let ir = IrProgram::compile(&self.code);
+        let blocks: BTreeMap<usize, &Block> = ir.blocks().map(|b| (b.start, b)).collect();
+        let mut entries = vec![0];
+        entries.extend(ir.functions().into_iter().filter(|entry| *entry != 0));
+        let bases: Vec<_> = entries
+            .iter()
+            .map(|entry| relative_bases(&blocks, *entry))
+            .collect();
+
+        // Arguments are stored above the relative base of the caller, right before the call,
+        // and the cells of the main function are addressed from a relative base of 0
+        let mut params: BTreeMap<usize, i64> = BTreeMap::new();
+        let mut written = BTreeSet::new();
+        for (idx, function) in bases.iter().enumerate() {
+            for (start, base) in function.iter() {
+                let block = blocks[start];
+                let (op_bases, end) = op_bases(block, *base);
+                for ((_, op), op_base) in block.ops.iter().zip(op_bases) {
+                    match (destination(op), op_base) {
+                        (Some(Value::Position(addr)), _) => {
+                            written.insert(*addr);
+                        }
+                        (Some(Value::Relative(offset)), Some(op_base)) => {
+                            let addr = match op_base.checked_add(*offset) {
+                                Some(addr) => addr,
+                                None => continue,
+                            };
+                            if idx == 0 && addr >= 0 {
+                                written.insert(addr as usize);
+                            }
+                            if let (Terminator::Call { target, .. }, Some(slot)) =
+                                (&block.terminator, end.and_then(|end| addr.checked_sub(end)))
+                            {
+                                let count = params.entry(*target).or_insert(0);
+                                *count = (*count).max(slot);
+                            }
+                        }
+                        _ => (),
+                    }
+                }
+            }
+        }
+
+        let mut translated = vec![];
+        for (entry, function) in entries.iter().zip(bases.iter()) {
+            let prologue = match blocks[entry].ops.first() {
+                Some((
+                    _,
+                    Op::AdjustBase {
+                        delta: Value::Immediate(size),
+                    },
+                )) if *size > 0 => *size,
+                _ => 0,
+            };
+            let count = params.get(entry).cloned().unwrap_or(0);
+            let translator = Translator {
+                decompiler: self,
+                written: &written,
+                params: &params,
+                frame: Frame {
+                    main: *entry == 0,
+                    params: count,
+                    size: prologue.max(count + 1),
+                },
+            };
+            let mut function: BTreeMap<usize, (Vec<Stmt>, Exit)> = function
+                .iter()
+                .map(|(start, base)| (*start, translator.block(&blocks, *start, *base)))
+                .collect();
+            fold_args(&mut function);
+            translated.push((*entry, count as usize, function));
+        }
+
+        // Variables written and read once are temporaries. The cells of the frames are
+        // specific to each function, other cells are shared by the whole program
+        let global = |name: &str| {
+            name.strip_prefix('m')
+                .is_some_and(|addr| addr.parse::<usize>().is_ok())
+                || self.names.values().any(|n| n == name)
+        };
+        let mut counts: BTreeMap<(usize, String), (usize, usize)> = BTreeMap::new();
+        for (entry, _, function) in translated.iter() {
+            let scope = |name: &str| {
+                let scope = if *entry == 0 || global(name) {
+                    0
+                } else {
+                    *entry
+                };
+                (scope, name.to_string())
+            };
+            for (stmts, exit) in function.values() {
+                for stmt in stmts.iter().chain(match exit {
+                    Exit::End(stmt) => Some(stmt),
+                    _ => None,
+                }) {
+                    stmt.visit(&mut |name| counts.entry(scope(name)).or_default().1 += 1);
+                    if let Some(name) = stmt.writes() {
+                        counts.entry(scope(name)).or_default().0 += 1;
+                    }
+                }
+                if let Exit::Branch { cond, .. } = exit {
+                    cond.visit(&mut |name| counts.entry(scope(name)).or_default().1 += 1);
+                }
+            }
+        }
+
+        let functions = translated
+            .into_iter()
+            .map(|(entry, params, mut function)| {
+                let scope = if entry == 0 { 0 } else { entry };
+                for (stmts, exit) in function.values_mut() {
+                    fold(stmts, exit, |name| {
+                        let scope = if global(name) { 0 } else { scope };
+                        counts.get(&(scope, name.to_string())) == Some(&(1, 1))
+                    });
+                }
+                let function: BTreeMap<usize, (Vec<Stmt>, Exit)> = function
+                    .into_iter()
+                    .map(|(start, (stmts, exit))| {
+                        let stmts = stmts.into_iter().map(|s| s.map_exprs(&Expr::simplify));
+                        let exit = match exit {
+                            Exit::Branch { cond, target, next } => Exit::Branch {
+                                cond: cond.simplify(),
+                                target,
+                                next,
+                            },
+                            Exit::End(stmt) => Exit::End(stmt.map_exprs(&Expr::simplify)),
+                            exit => exit,
+                        };
+                        (start, (stmts.collect(), exit))
+                    })
+                    .collect();
+                Function {
+                    entry,
+                    params,
+                    body: Structurer::structure(&function, entry),
+                }
+            })
+            .collect();
+        Decompiled { functions }
+    }
+}
+
+/// Translates the blocks of a function into statements
+struct Translator<'a> {
+    decompiler: &'a Decompiler,
+    written: &'a BTreeSet<usize>,
+    params: &'a BTreeMap<usize, i64>,
+    frame: Frame,
+}
+
+impl Translator<'_> {
+    fn global(&self, addr: usize) -> String {
+        match self.decompiler.names.get(&addr) {
+            Some(name) => name.clone(),
+            None => format!("m{}", addr),
+        }
+    }
+
+    /// Address of a cell of the main function
+    fn address(&self, value: &Value, base: Option<i64>) -> Option<usize> {
+        match (value, base) {
+            (Value::Position(addr), _) => Some(*addr),
+            (Value::Relative(offset), Some(base)) if self.frame.main => base
+                .checked_add(*offset)
+                .filter(|addr| *addr >= 0)
+                .map(|addr| addr as usize),
+            _ => None,
+        }
+    }
+
+    /// Name of the variable of a cell, given the relative base from the entry of the function
+    fn variable(&self, value: &Value, base: Option<i64>) -> String {
+        if let Some(addr) = self.address(value, base) {
+            return self.global(addr);
+        }
+        let slot = base.and_then(|base| match value {
+            Value::Relative(offset) => base.checked_add(*offset),
+            _ => None,
+        });
+        match (value, slot) {
+            (Value::Immediate(value), _) => value.to_string(),
+            (Value::Relative(offset), None) => format!("rb[{}]", offset),
+            (Value::Relative(_), Some(slot)) => {
+                let frame = &self.frame;
+                match slot {
+                    _ if frame.main || slot < 0 => format!("frame[{}]", slot),
+                    0 => "return_address".to_string(),
+                    _ if slot <= frame.params => format!("p{}", slot),
+                    _ if slot < frame.size => format!("v{}", slot),
+                    _ => format!("out{}", slot - frame.size),
+                }
+            }
+            (Value::Position(_), _) => unreachable!(),
+        }
+    }
+
+    fn read(&self, value: &Value, base: Option<i64>) -> Expr {
+        if let Value::Immediate(value) = value {
+            return Expr::Const(*value);
+        }
+        let decompiler = self.decompiler;
+        match self.address(value, base) {
+            Some(addr)
+                if decompiler.constant_memory
+                    && !self.written.contains(&addr)
+                    && !decompiler.names.contains_key(&addr) =>
+            {
+                Expr::Const(decompiler.code.get(addr).cloned().unwrap_or(0))
+            }
+            _ => Expr::Var(self.variable(value, base)),
+        }
+    }
+
+    /// Translates a block, up to the next block starting inside it
+    fn block(
+        &self,
+        blocks: &BTreeMap<usize, &Block>,
+        start: usize,
+        base: Option<i64>,
+    ) -> (Vec<Stmt>, Exit) {
+        let block = blocks[&start];
+        let split = blocks
+            .range(start + 1..block.exit.max(start) + 1)
+            .next()
+            .map(|(addr, _)| *addr);
+        let (bases, end) = op_bases(block, base);
+        let mut stmts = vec![];
+        for ((addr, op), base) in block.ops.iter().zip(bases.iter().cloned()) {
+            if let Some(split) = split.filter(|split| addr >= split) {
+                return (stmts, Exit::Goto(split));
+            }
+            let stmt = match op {
+                // Copies of a cell to itself
+                Op::Copy { dst, src } if dst == src => continue,
+                Op::Copy { dst, src } => {
+                    Stmt::Assign(self.variable(dst, base), self.read(src, base))
+                }
+                Op::Binary { op, dst, a, b } => Stmt::Assign(
+                    self.variable(dst, base),
+                    Expr::binary(*op, self.read(a, base), self.read(b, base)),
+                ),
+                Op::Input { dst } => Stmt::Assign(self.variable(dst, base), Expr::Input),
+                Op::Output { src } => Stmt::Output(self.read(src, base)),
+                Op::AdjustBase {
+                    delta: Value::Immediate(_),
+                } if base.is_some() => continue,
+                Op::AdjustBase { delta } => Stmt::AdjustBase(self.read(delta, base)),
+            };
+            stmts.push(stmt);
+        }
+        if let Some(split) = split {
+            return (stmts, Exit::Goto(split));
+        }
+
+        let exit = match &block.terminator {
+            Terminator::Jump(target) => Exit::Goto(*target),
+            Terminator::Branch {
+                cond,
+                if_zero,
+                target,
+                next,
+            } => {
+                let mut cond = self.read(cond, end);
+                if *if_zero {
+                    cond = cond.not();
+                }
+                match target {
+                    Value::Immediate(target) if *target >= 0 => Exit::Branch {
+                        cond,
+                        target: *target as usize,
+                        next: *next,
+                    },
+                    _ => {
+                        stmts.push(Stmt::If {
+                            cond,
+                            then: vec![Stmt::GotoComputed(self.read(target, end))],
+                            otherwise: vec![],
+                        });
+                        Exit::Goto(*next)
+                    }
+                }
+            }
+            Terminator::Call { target, returns_to } => {
+                self.call(&mut stmts, *target, *returns_to, end);
+                Exit::Goto(*returns_to)
+            }
+            Terminator::Return { target } | Terminator::Computed { target } => {
+                if !self.frame.main && self.variable(target, end) == "return_address" {
+                    Exit::End(Stmt::Return)
+                } else {
+                    Exit::End(Stmt::GotoComputed(self.read(target, end)))
+                }
+            }
+            Terminator::Halt => Exit::End(Stmt::Halt),
+            Terminator::Interpret => Exit::End(Stmt::Invalid(block.exit)),
+        };
+        (stmts, exit)
+    }
+
+    /// Turns the store of the return address before a call into a call statement,
+    /// reading its arguments from their slots
+    fn call(&self, stmts: &mut Vec<Stmt>, function: usize, returns_to: usize, base: Option<i64>) {
+        let slot = |offset: i64| self.variable(&Value::Relative(offset), base);
+        let return_address = Stmt::Assign(slot(0), Expr::Const(returns_to as i64));
+        if let Some(idx) = stmts.iter().rposition(|stmt| *stmt == return_address) {
+            stmts.remove(idx);
+        }
+        let args = (1..=self.params.get(&function).cloned().unwrap_or(0))
+            .map(|offset| Expr::Var(slot(offset)))
+            .collect();
+        stmts.push(Stmt::Call { function, args });
+    }
+}
+
+/// Whether a variable can be read after the statement `idx` of a block, before being written
+fn read_after(
+    function: &BTreeMap<usize, (Vec<Stmt>, Exit)>,
+    start: usize,
+    idx: usize,
+    name: &str,
+) -> bool {
+    let mut visited = BTreeSet::new();
+    let mut todo = vec![(start, idx + 1)];
+    'blocks: while let Some((start, from)) = todo.pop() {
+        // Jumps out of the function may read anything
+        let (stmts, exit) = match function.get(&start) {
+            Some(block) => block,
+            None => return true,
+        };
+        for stmt in stmts[from..].iter() {
+            if stmt.reads(name) {
+                return true;
+            }
+            if stmt.writes().is_some_and(|written| written == name) {
+                continue 'blocks;
+            }
+        }
+        match exit {
+            Exit::End(Stmt::GotoComputed(_)) | Exit::End(Stmt::Invalid(_)) => return true,
+            exit if exit.reads(name) => return true,
+            exit => {
+                for next in exit.successors() {
+                    if visited.insert(next) {
+                        todo.push((next, 0));
+                    }
+                }
+            }
+        }
+    }
+    false
+}
+
+/// Moves the stores of the arguments of the calls into the call statements, unless the
+/// caller reads their slot after the call: the callee may have written it
+fn fold_args(function: &mut BTreeMap<usize, (Vec<Stmt>, Exit)>) {
+    let starts: Vec<usize> = function.keys().cloned().collect();
+    for start in starts {
+        let stmts = &function[&start].0;
+        let call = match stmts.iter().rposition(|s| matches!(s, Stmt::Call { .. })) {
+            Some(call) => call,
+            None => continue,
+        };
+        let foldable: Vec<(usize, String)> = match &stmts[call] {
+            Stmt::Call { args, .. } => args
+                .iter()
+                .enumerate()
+                .filter_map(|(arg, expr)| match expr {
+                    Expr::Var(name) if !read_after(function, start, call, name) => {
+                        Some((arg, name.clone()))
+                    }
+                    _ => None,
+                })
+                .collect(),
+            _ => unreachable!(),
+        };
+
+        let stmts = &mut function.get_mut(&start).unwrap().0;
+        for (arg, name) in foldable {
+            let call = stmts.len() - 1;
+            let store = stmts[..call]
+                .iter()
+                .rposition(|stmt| stmt.writes() == Some(&name))
+                .filter(|idx| match &stmts[*idx] {
+                    Stmt::Assign(_, expr) => can_move(&name, expr, &stmts[idx + 1..call]),
+                    _ => false,
+                });
+            if let Some(Stmt::Assign(_, expr)) = store.map(|idx| stmts.remove(idx)) {
+                if let Some(Stmt::Call { args, .. }) = stmts.last_mut() {
+                    args[arg] = expr;
+                }
+            }
+        }
+    }
+}
+
+/// Successor standing for the end of the function
+const END: usize = usize::MAX;
+
+/// Immediate post-dominator of each block: the block where the paths from its branch join.
+/// None when they only join at the end of the function
+fn post_dominators(successors: &BTreeMap<usize, Vec<usize>>) -> BTreeMap<usize, Option<usize>> {
+    // Jumps out of the function end it
+    let next = |node: &usize| -> Vec<usize> {
+        let next: Vec<usize> = successors[node]
+            .iter()
+            .map(|s| if successors.contains_key(s) { *s } else { END })
+            .collect();
+        if next.is_empty() {
+            vec![END]
+        } else {
+            next
+        }
+    };
+    let mut ends: BTreeSet<usize> = vec![END].into_iter().collect();
+    let all: BTreeSet<usize> = successors.keys().cloned().chain(ends.clone()).collect();
+    let mut pdom: BTreeMap<usize, BTreeSet<usize>> =
+        successors.keys().map(|n| (*n, all.clone())).collect();
+    pdom.insert(END, ends.clone());
+    let mut changed = true;
+    while changed {
+        changed = false;
+        for node in successors.keys().rev() {
+            let mut set: Option<BTreeSet<usize>> = None;
+            for s in next(node) {
+                set = Some(match set {
+                    Some(set) => set.intersection(&pdom[&s]).cloned().collect(),
+                    None => pdom[&s].clone(),
+                });
+                if ends.contains(&s) && ends.insert(*node) {
+                    changed = true;
+                }
+            }
+            let mut set = set.unwrap();
+            set.insert(*node);
+            if set != pdom[node] {
+                pdom.insert(*node, set);
+                changed = true;
+            }
+        }
+    }
+    successors
+        .keys()
+        .map(|node| {
+            let set = &pdom[node];
+            let join = set
+                .iter()
+                .filter(|p| *p != node && **p != END)
+                .find(|p| pdom[p].len() + 1 == set.len())
+                .cloned();
+            (*node, join.filter(|_| ends.contains(node)))
+        })
+        .collect()
+}
+
+/// Loop headers, the targets of the back edges of a depth first search, with the block following each loop
+fn loops(
+    successors: &BTreeMap<usize, Vec<usize>>,
+    joins: &BTreeMap<usize, Option<usize>>,
+    entry: usize,
+) -> BTreeMap<usize, Option<usize>> {
+    let mut back_edges: BTreeMap<usize, Vec<usize>> = BTreeMap::new();
+    // Whether each visited block is on the stack
+    let mut on_stack: BTreeMap<usize, bool> = BTreeMap::new();
+    let mut stack = vec![];
+    if successors.contains_key(&entry) {
+        on_stack.insert(entry, true);
+        stack.push((entry, 0));
+    }
+    while let Some((node, idx)) = stack.pop() {
+        match successors[&node].get(idx) {
+            Some(next) => {
+                stack.push((node, idx + 1));
+                match on_stack.get(next) {
+                    Some(true) => back_edges.entry(*next).or_default().push(node),
+                    Some(false) => (),
+                    None if successors.contains_key(next) => {
+                        on_stack.insert(*next, true);
+                        stack.push((*next, 0));
+                    }
+                    None => (),
+                }
+            }
+            None => {
+                on_stack.insert(node, false);
+            }
+        }
+    }
+
+    let mut predecessors: BTreeMap<usize, Vec<usize>> = BTreeMap::new();
+    for (node, next) in successors.iter() {
+        for s in next {
+            predecessors.entry(*s).or_default().push(*node);
+        }
+    }
+    back_edges
+        .into_iter()
+        .map(|(header, sources)| {
+            // Blocks reaching a back edge without going through the header
+            let mut body: BTreeSet<usize> = vec![header].into_iter().collect();
+            let mut todo = sources;
+            while let Some(node) = todo.pop() {
+                if body.insert(node) {
+                    todo.extend(predecessors.get(&node).into_iter().flatten());
+                }
+            }
+            let exit = match joins[&header] {
+                Some(join) if !body.contains(&join) => Some(join),
+                _ => body
+                    .iter()
+                    .flat_map(|node| successors[node].iter())
+                    .filter(|s| !body.contains(s))
+                    .min()
+                    .cloned(),
+            };
+            (header, exit)
+        })
+        .collect()
+}
+
+/// Recovers the loops and conditionals of a function from its blocks
+struct Structurer<'a> {
+    blocks: &'a BTreeMap<usize, (Vec<Stmt>, Exit)>,
+    joins: BTreeMap<usize, Option<usize>>,
+    /// Loop headers, with the block following the loop
+    loops: BTreeMap<usize, Option<usize>>,
+    visited: BTreeSet<usize>,
+    gotos: BTreeSet<usize>,
+    /// Header and exit of the innermost loop
+    context: Option<(usize, Option<usize>)>,
+    /// Blocks where the branches being emitted join
+    stops: Vec<usize>,
+}
+
+impl<'a> Structurer<'a> {
+    fn structure(blocks: &'a BTreeMap<usize, (Vec<Stmt>, Exit)>, entry: usize) -> Vec<Stmt> {
+        let successors: BTreeMap<usize, Vec<usize>> = blocks
+            .iter()
+            .map(|(start, (_, exit))| (*start, exit.successors()))
+            .collect();
+        let joins = post_dominators(&successors);
+        let loops = loops(&successors, &joins, entry);
+        let mut structurer = Structurer {
+            blocks,
+            joins,
+            loops,
+            visited: BTreeSet::new(),
+            gotos: BTreeSet::new(),
+            context: None,
+            stops: vec![],
+        };
+        let mut body = vec![];
+        structurer.sequence(Some(entry), &mut body);
+        tidy(body, &structurer.gotos)
+    }
+
+    /// Emits the blocks from the given one, until reaching the join of the enclosing branch
+    fn sequence(&mut self, mut node: Option<usize>, out: &mut Vec<Stmt>) {
+        while let Some(n) = node {
+            if self.stops.contains(&n) {
+                return;
+            }
+            if let Some((header, exit)) = self.context {
+                if n == header {
+                    out.push(Stmt::Continue);
+                    return;
+                }
+                if Some(n) == exit {
+                    out.push(Stmt::Break);
+                    return;
+                }
+            }
+            if !self.blocks.contains_key(&n) {
+                out.push(Stmt::Invalid(n));
+                return;
+            }
+            if !self.visited.insert(n) {
+                self.gotos.insert(n);
+                out.push(Stmt::Goto(n));
+                return;
+            }
+            out.push(Stmt::Label(n));
+            node = match self.loops.get(&n).cloned() {
+                Some(exit) => {
+                    // Branches outside of the loop cannot join inside it
+                    let context = self.context.replace((n, exit));
+                    let stops = std::mem::take(&mut self.stops);
+                    let mut body = vec![];
+                    let next = self.block(n, &mut body);
+                    self.sequence(next, &mut body);
+                    self.context = context;
+                    self.stops = stops;
+                    out.push(Stmt::While {
+                        cond: Expr::Const(1),
+                        body,
+                    });
+                    exit
+                }
+                None => self.block(n, out),
+            };
+        }
+    }
+
+    /// Emits a block, with both sides of its branch. Returns the block following it
+    fn block(&mut self, n: usize, out: &mut Vec<Stmt>) -> Option<usize> {
+        let blocks = self.blocks;
+        let (stmts, exit) = &blocks[&n];
+        out.extend(stmts.iter().cloned());
+        match exit {
+            Exit::Goto(target) => Some(*target),
+            Exit::End(stmt) => {
+                out.push(stmt.clone());
+                None
+            }
+            Exit::Branch { cond, target, next } => {
+                let join = self.joins[&n];
+                self.stops.extend(join);
+                let mut then = vec![];
+                self.sequence(Some(*next), &mut then);
+                let mut otherwise = vec![];
+                self.sequence(Some(*target), &mut otherwise);
+                if join.is_some() {
+                    self.stops.pop();
+                }
+                // The next block follows when the jump is not taken
+                out.push(Stmt::If {
+                    cond: cond.clone().not(),
+                    then,
+                    otherwise,
+                });
+                join
+            }
+        }
+    }
+}
+
+/// Removes the unused labels and the unreachable statements, and recognizes `while` loops
+fn tidy(stmts: Vec<Stmt>, gotos: &BTreeSet<usize>) -> Vec<Stmt> {
+    let mut out = vec![];
+    let mut reachable = true;
+    for stmt in stmts {
+        let stmt = match stmt {
+            Stmt::Label(addr) if !gotos.contains(&addr) => continue,
+            Stmt::Label(addr) => Stmt::Label(addr),
+            _ if !reachable => continue,
+            Stmt::If {
+                cond,
+                then,
+                otherwise,
+            } => {
+                let (then, otherwise) = (tidy(then, gotos), tidy(otherwise, gotos));
+                match (then.is_empty(), otherwise.is_empty()) {
+                    (true, true) if !cond.reads_input() => continue,
+                    (true, false) => Stmt::If {
+                        cond: cond.not(),
+                        then: otherwise,
+                        otherwise: then,
+                    },
+                    (false, false) if matches!(cond, Expr::Not(_)) => Stmt::If {
+                        cond: cond.not(),
+                        then: otherwise,
+                        otherwise: then,
+                    },
+                    _ => Stmt::If {
+                        cond,
+                        then,
+                        otherwise,
+                    },
+                }
+            }
+            Stmt::While { cond, body } => while_loop(cond, tidy(body, gotos)),
+            stmt => stmt,
+        };
+        reachable = !stmt.is_jump();
+        out.push(stmt);
+    }
+    out
+}
+
+/// Moves the condition at the start of a `while (1)` loop into the loop
+fn while_loop(cond: Expr, mut body: Vec<Stmt>) -> Stmt {
+    if body.last() == Some(&Stmt::Continue) {
+        body.pop();
+    }
+    if cond != Expr::Const(1) {
+        return Stmt::While { cond, body };
+    }
+    match body.as_slice() {
+        // while (1) { if (c) break; ... }
+        [Stmt::If {
+            cond,
+            then,
+            otherwise,
+        }, ..]
+            if *then == [Stmt::Break] && otherwise.is_empty() =>
+        {
+            let cond = cond.clone().not();
+            body.remove(0);
+            Stmt::While { cond, body }
+        }
+        // while (1) { if (c) { ...; continue; } break; }
+        [Stmt::If {
+            cond,
+            then,
+            otherwise,
+        }, Stmt::Break]
+            if otherwise.is_empty() && then.last() == Some(&Stmt::Continue) =>
+        {
+            let mut then = then.clone();
+            then.pop();
+            Stmt::While {
+                cond: cond.clone(),
+                body: then,
+            }
+        }
+        // while (1) { ...; if (c) continue; break; }
+        [.., Stmt::If {
+            cond,
+            then,
+            otherwise,
+        }, Stmt::Break]
+            if *then == [Stmt::Continue]
+                && otherwise.is_empty()
+                && !continues(&body[..body.len() - 2]) =>
+        {
+            let cond = cond.clone();
+            body.truncate(body.len() - 2);
+            Stmt::DoWhile { body, cond }
+        }
+        _ => Stmt::While { cond, body },
+    }
+}
+
+/// Whether the statements continue their loop, which starts over a `do ... while` loop
+/// instead of testing its condition
+fn continues(stmts: &[Stmt]) -> bool {
+    stmts.iter().any(|stmt| match stmt {
+        Stmt::Continue => true,
+        Stmt::If {
+            then, otherwise, ..
+        } => continues(then) || continues(otherwise),
+        _ => false,
+    })
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::{Decompiler, Expr, Stmt};
+    use crate::intcode_computer::assembler::assemble;
+    use crate::intcode_computer::parse_input;
+
+    #[test]
+    fn decompiler_day2_algebra() {
+        // Computes [0] = [1] * [13] + [14] + [2], as the day 2 programs do
+        let code = parse_input("2,1,13,16,1,16,14,17,1,17,2,0,99,5,7,0,0,0");
+        let decompiled = Decompiler::new(&code).decompile();
+        assert_eq!(
+            decompiled.to_string(),
+            "void main() {\n    m0 = m1 * m13 + m14 + m2;\n    halt();\n}\n"
+        );
+
+        let decompiled = Decompiler::new(&code)
+            .name(1, "noun")
+            .name(2, "verb")
+            .constant_memory()
+            .decompile();
+        assert_eq!(
+            decompiled.to_string(),
+            "void main() {\n    m0 = 5 * noun + verb + 7;\n    halt();\n}\n"
+        );
+
+        // The relative address overflows
+        let decompiled = Decompiler::new(&[109, 1, 204, i64::MAX, 99]).decompile();
+        assert!(decompiled
+            .to_string()
+            .contains("output(rb[9223372036854775807]);"));
+    }
+
+    #[test]
+    fn decompiler_substitution() {
+        let var = |name: &str| Expr::Var(name.to_string());
+        let mut stmt = Stmt::If {
+            cond: var("c"),
+            then: vec![Stmt::Output(var("t"))],
+            otherwise: vec![
+                Stmt::Output(var("t")),
+                Stmt::Assign("u".to_string(), var("t")),
+            ],
+        };
+        assert!(stmt.substitute("t", &Expr::Const(3)));
+        assert!(!stmt.reads("t"));
+        assert!(stmt.reads("c"));
+    }
+
+    #[test]
+    fn decompiler_functions_and_loops() {
+        let source = "
+                    ARB #stack
+                    ADD #5, #0, [rb+1]
+                    ADD #ret, #0, [rb+0]
+                    JT #1, #count
+            ret:    OUT [rb+1]
+                    HLT
+            ; Outputs its argument down to 1, then returns 42
+            count:  ARB #2
+            loop:   JF [rb-1], #done
+                    OUT [rb-1]
+                    ADD [rb-1], #-1, [rb-1]
+                    JT #1, #loop
+            done:   ADD #42, #0, [rb-1]
+                    ARB #-2
+                    JT #1, [rb+0]
+            stack:  DB 0
+            ";
+        let code = assemble(source).unwrap();
+        let decompiled = Decompiler::new(&code).decompile();
+        assert_eq!(decompiled.functions().len(), 2);
+        assert_eq!(decompiled.function(16).unwrap().params, 1);
+        assert_eq!(
+            decompiled.to_string(),
+            "\
+void main() {
+    m40 = 5;
+    f16(m40);
+    output(m40);
+    halt();
+}
+
+void f16(int &p1) {
+    while (p1) {
+        output(p1);
+        p1 = p1 - 1;
+    }
+    p1 = 42;
+    return;
+}
+"
+        );
+
+        // The argument is passed by value when its slot is not read after the call
+        let code = assemble(&source.replace("OUT [rb+1]", "OUT #7")).unwrap();
+        let decompiled = Decompiler::new(&code).decompile();
+        assert_eq!(
+            decompiled.function(0).unwrap().to_string(),
+            "void main() {\n    f16(5);\n    output(7);\n    halt();\n}\n"
+        );
+    }
+}
@@ -0,0 +1,314 @@
+use super::instruction_set::InstructionSet;
+use super::{Instruction, Memory, Value};
+use std::collections::{BTreeMap, BTreeSet};
+
+/// Maximum number of data cells displayed on a single `DB` line
+const DATA_PER_LINE: usize = 8;
+
+/// A decoded part of an Intcode image
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Item {
+    /// An instruction reachable from address 0
+    Code(Instruction),
+    /// A cell that is never executed
+    Data(i64),
+}
+
+/// Where the execution can go after an instruction
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Flow {
+    /// Statically known jump targets
+    pub targets: Vec<usize>,
+    /// Whether the execution can continue with the next instruction
+    pub falls_through: bool,
+    /// Whether the instruction can jump to an address only known at runtime
+    pub computed: bool,
+}
+
+/// Computes the control flow of an instruction.
+/// Instructions of registered opcodes are assumed to continue with the next one
+pub fn flow(instr: &Instruction) -> Flow {
+    let (cond, target, jump_if) = match instr {
+        Instruction::Halt => {
+            return Flow {
+                targets: vec![],
+                falls_through: false,
+                computed: false,
+            }
+        }
+        Instruction::JumpIfTrue(cond, target) => (cond, target, true),
+        Instruction::JumpIfFalse(cond, target) => (cond, target, false),
+        _ => {
+            return Flow {
+                targets: vec![],
+                falls_through: true,
+                computed: false,
+            }
+        }
+    };
+    // A jump on an immediate condition is either always or never taken
+    let (taken, falls_through) = match cond {
+        Value::Immediate(c) if (*c != 0) == jump_if => (true, false),
+        Value::Immediate(_) => (false, true),
+        _ => (true, true),
+    };
+    let mut flow = Flow {
+        targets: vec![],
+        falls_through,
+        computed: false,
+    };
+    if taken {
+        match target {
+            Value::Immediate(t) if *t >= 0 => flow.targets.push(*t as usize),
+            Value::Immediate(_) => (),
+            _ => flow.computed = true,
+        }
+    }
+    flow
+}
+
+/// Returns the constant an instruction stores, if it only uses immediate operands
+pub fn constant_store(instr: &Instruction) -> Option<i64> {
+    match instr {
+        Instruction::Add(Value::Immediate(a), Value::Immediate(b), _) => a.checked_add(*b),
+        Instruction::Mul(Value::Immediate(a), Value::Immediate(b), _) => a.checked_mul(*b),
+        _ => None,
+    }
+}
+
+/// Tells whether an instruction always jumps to a statically known address
+pub fn is_unconditional_jump(instr: &Instruction) -> bool {
+    let flow = flow(instr);
+    !flow.falls_through && flow.targets.len() == 1
+}
+
+/// Disassembled Intcode image
+#[derive(Debug, Clone)]
+pub struct Listing {
+    /// Decoded items, by address
+    items: BTreeMap<usize, Item>,
+    /// Addresses that are the target of a jump (or a return)
+    labels: BTreeSet<usize>,
+}
+
+impl Listing {
+    /// Decoded items, by address
+    pub fn items(&self) -> impl Iterator<Item = (&usize, &Item)> {
+        self.items.iter()
+    }
+
+    /// Tells whether the given address is the start of a reachable instruction
+    pub fn is_code(&self, addr: usize) -> bool {
+        matches!(self.items.get(&addr), Some(Item::Code(_)))
+    }
+
+    /// Addresses that are the target of a jump (or a return)
+    pub fn labels(&self) -> impl Iterator<Item = &usize> {
+        self.labels.iter()
+    }
+
+    /// Name of the label at the given address
+    pub fn label_name(addr: usize) -> String {
+        format!("L{}", addr)
+    }
+
+    /// Formats an instruction, replacing the known jump targets by their label
+    fn format_instruction(&self, instr: &Instruction) -> String {
+        match instr {
+            Instruction::JumpIfTrue(cond, Value::Immediate(t))
+            | Instruction::JumpIfFalse(cond, Value::Immediate(t))
+                if *t >= 0 && self.labels.contains(&(*t as usize)) =>
+            {
+                format!(
+                    "{} {}, #{}",
+                    instr.mnemonic(),
+                    cond,
+                    Listing::label_name(*t as usize)
+                )
+            }
+            _ => instr.to_string(),
+        }
+    }
+
+    /// Lines of the listing, with the address of their first cell.
+    /// Label lines are attached to the address they label
+    pub fn lines(&self) -> Vec<(usize, String)> {
+        let mut lines = vec![];
+        let mut data: Vec<i64> = vec![];
+        let mut data_start = 0;
+        let flush = |lines: &mut Vec<(usize, String)>, data: &mut Vec<i64>, start: usize| {
+            if !data.is_empty() {
+                let values: Vec<String> = data.iter().map(|d| d.to_string()).collect();
+                lines.push((start, format!("    DB {}", values.join(", "))));
+                data.clear();
+            }
+        };
+        for (addr, item) in self.items.iter() {
+            if self.labels.contains(addr) || data.len() == DATA_PER_LINE {
+                flush(&mut lines, &mut data, data_start);
+            }
+            if self.labels.contains(addr) {
+                lines.push((*addr, format!("{}:", Listing::label_name(*addr))));
+            }
+            match item {
+                Item::Code(instr) => {
+                    flush(&mut lines, &mut data, data_start);
+                    lines.push((*addr, format!("    {}", self.format_instruction(instr))));
+                }
+                Item::Data(value) => {
+                    if data.is_empty() {
+                        data_start = *addr;
+                    }
+                    data.push(*value);
+                }
+            }
+        }
+        flush(&mut lines, &mut data, data_start);
+        lines
+    }
+}
+
+impl std::fmt::Display for Listing {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        for (addr, line) in self.lines() {
+            if line.ends_with(':') {
+                writeln!(f, "{}", line)?;
+            } else {
+                writeln!(f, "{:<40}; {}", line, addr)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Disassembles an Intcode image, following the control flow from address 0
+/// to tell code apart from data
+pub fn disassemble(code: &[i64]) -> Listing {
+    disassemble_with(code, &InstructionSet::standard())
+}
+
+/// Disassembles an Intcode image, decoding the opcodes of the given instruction set
+pub fn disassemble_with(code: &[i64], set: &InstructionSet) -> Listing {
+    let memory = Memory::new(code.to_vec());
+    let mut instructions: BTreeMap<usize, Instruction> = BTreeMap::new();
+    let mut labels = BTreeSet::new();
+    let mut todo = vec![0];
+
+    while let Some(addr) = todo.pop() {
+        if addr >= code.len() || instructions.contains_key(&addr) {
+            continue;
+        }
+        let instr = match set.decode(&memory, addr) {
+            Ok(instr) => instr,
+            Err(_) => continue,
+        };
+        let next = addr + instr.args_count() + 1;
+        let instr_flow = flow(&instr);
+        for target in instr_flow.targets {
+            labels.insert(target);
+            todo.push(target);
+        }
+        if instr_flow.falls_through {
+            todo.push(next);
+        }
+        // Calls store their return address right before jumping to the function:
+        // the return address is then reached through a computed jump
+        if let Some(ret) = constant_store(&instr) {
+            if let Ok(jump) = set.decode(&memory, next) {
+                if is_unconditional_jump(&jump) && ret == (next + jump.args_count() + 1) as i64 {
+                    labels.insert(ret as usize);
+                    todo.push(ret as usize);
+                }
+            }
+        }
+        instructions.insert(addr, instr);
+    }
+
+    // An instruction overlapping another one, or a label, is emitted as data so that
+    // every reachable address keeps its own line
+    let mut items = BTreeMap::new();
+    let mut addr = 0;
+    while addr < code.len() {
+        let instr = instructions.remove(&addr);
+        let overlapped = |instr: &Instruction| {
+            (addr + 1..addr + instr.args_count() + 1)
+                .any(|a| instructions.contains_key(&a) || labels.contains(&a))
+        };
+        match instr {
+            Some(instr) if !overlapped(&instr) => {
+                let next = addr + instr.args_count() + 1;
+                items.insert(addr, Item::Code(instr));
+                addr = next;
+            }
+            _ => {
+                items.insert(addr, Item::Data(code[addr]));
+                addr += 1;
+            }
+        }
+    }
+    Listing { items, labels }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::disassemble;
+    use crate::intcode_computer::assembler::assemble;
+    use crate::intcode_computer::parse_input;
+
+    #[test]
+    fn disassembler_listing() {
+        // Counts down from 3, outputting each value, then halts. Ends with data
+        let input = parse_input("1101,3,0,17,4,17,1001,17,-1,17,1005,17,4,99,7,8,9,0");
+        let listing = disassemble(&input);
+        assert!(listing.is_code(0));
+        assert!(listing.is_code(13));
+        assert!(!listing.is_code(14));
+
+        let lines: Vec<String> = listing.lines().into_iter().map(|(_, l)| l).collect();
+        assert_eq!(
+            lines,
+            vec![
+                "    ADD #3, #0, [17]",
+                "L4:",
+                "    OUT [17]",
+                "    ADD [17], #-1, [17]",
+                "    JT [17], #L4",
+                "    HLT",
+                "    DB 7, 8, 9, 0",
+            ]
+        );
+    }
+
+    #[test]
+    fn disassembler_follows_calls() {
+        // Calls a function at 10 that outputs its return address, then returns to 9
+        let input = parse_input("109,20,21101,9,0,0,1105,1,10,99,204,0,2105,1,0");
+        let listing = disassemble(&input);
+        let text = listing.to_string();
+        assert!(listing.is_code(9));
+        assert!(text.contains("L9:\n    HLT"));
+        assert!(text.contains("    ARB #20"));
+        assert!(text.contains("    JT #1, #L10"));
+        assert!(text.contains("    OUT [rb+0]"));
+        assert!(text.contains("    JT #1, [rb+0]"));
+
+        // Jumps to the operand of the instruction it falls through to
+        let input = parse_input("1005,6,4,104,99,99,1");
+        let listing = disassemble(&input);
+        assert!(!listing.is_code(3));
+        assert!(listing.is_code(4));
+        let source: Vec<String> = listing.lines().into_iter().map(|(_, l)| l).collect();
+        assert_eq!(
+            source,
+            vec![
+                "    JT [6], #L4",
+                "    DB 104",
+                "L4:",
+                "    HLT",
+                "    HLT",
+                "    DB 1"
+            ]
+        );
+        assert_eq!(assemble(&source.join("\n")), Ok(input));
+    }
+}
@@ -0,0 +1,317 @@
+use super::{ComputerError, Instruction, Memory, RunState, Value};
+use std::collections::VecDeque;
+
+/// Decoded instructions of the initial program, per address
+#[derive(Debug, Clone, Default)]
+struct Cache {
+    /// Grows up to the highest executed address of the initial program
+    slots: Vec<Option<Instruction>>,
+    /// Length of the initial program, the instructions past it are not cached
+    code_len: usize,
+}
+
+impl Cache {
+    fn get(&self, addr: usize) -> Option<&Instruction> {
+        self.slots.get(addr).and_then(|slot| slot.as_ref())
+    }
+
+    /// Caches an instruction of the initial program. Gives it back otherwise
+    fn insert(&mut self, addr: usize, instr: Instruction) -> Option<Instruction> {
+        if addr >= self.code_len {
+            return Some(instr);
+        }
+        if addr >= self.slots.len() {
+            self.slots.resize(addr + 1, None);
+        }
+        self.slots[addr] = Some(instr);
+        None
+    }
+
+    /// Forgets the decoded instructions overlapping the given address
+    fn invalidate(&mut self, addr: usize) {
+        let from = addr.saturating_sub(3).min(self.slots.len());
+        let to = (addr + 1).min(self.slots.len());
+        for slot in self.slots[from..to].iter_mut() {
+            *slot = None;
+        }
+    }
+}
+
+/// Intcode computer caching the decoded instructions, for long running programs.
+/// The cached instructions are forgotten when the program writes over them.
+///
+/// It executes programs exactly as `Computer`, but without its debugging features
+/// (traces, profiles, ports and budgets). Each step borrows its instruction from the cache,
+/// but the operands are still read through `Memory`. Programs running each instruction
+/// only once, such as the day 7 amplifiers, do not benefit from the cache
+#[derive(Debug, Clone)]
+pub struct FastComputer {
+    memory: Memory,
+    cache: Cache,
+    pointer: usize,
+    relative_base: i64,
+    input: VecDeque<i64>,
+    pub output: VecDeque<i64>,
+    halt_on_output: bool,
+    halted: bool,
+}
+
+impl FastComputer {
+    pub fn new(code: Vec<i64>) -> Self {
+        FastComputer {
+            cache: Cache {
+                slots: vec![],
+                code_len: code.len(),
+            },
+            memory: Memory::new(code),
+            pointer: 0,
+            relative_base: 0,
+            input: VecDeque::new(),
+            output: VecDeque::new(),
+            halt_on_output: false,
+            halted: false,
+        }
+    }
+
+    /// Stops the execution on each output, which is returned instead of queued
+    pub fn halt_on_output(mut self) -> Self {
+        self.halt_on_output = true;
+        self
+    }
+
+    pub fn input(&mut self, value: i64) {
+        self.input.push_back(value);
+    }
+
+    pub fn get_next_output(&mut self) -> Option<i64> {
+        self.output.pop_front()
+    }
+
+    pub fn get(&self, addr: usize) -> i64 {
+        self.memory.get(addr)
+    }
+
+    pub fn set(&mut self, addr: usize, value: i64) {
+        self.memory.set(addr, value);
+        self.cache.invalidate(addr);
+    }
+
+    pub fn pointer(&self) -> usize {
+        self.pointer
+    }
+
+    pub fn halted(&self) -> bool {
+        self.halted
+    }
+
+    fn address(&self, value: Value) -> Result<usize, ComputerError> {
+        let target = match value {
+            Value::Position(addr) => return Ok(addr),
+            Value::Relative(offset) => {
+                offset
+                    .checked_add(self.relative_base)
+                    .ok_or(ComputerError::Overflow {
+                        address: self.pointer,
+                    })?
+            }
+            Value::Immediate(_) => {
+                return Err(ComputerError::WriteToImmediate {
+                    address: self.pointer,
+                })
+            }
+        };
+        if target < 0 {
+            return Err(ComputerError::NegativeAddress {
+                address: self.pointer,
+                target,
+            });
+        }
+        Ok(target as usize)
+    }
+
+    fn load(&self, value: Value) -> Result<i64, ComputerError> {
+        match value {
+            Value::Immediate(x) => Ok(x),
+            Value::Position(addr) => Ok(self.memory.get(addr)),
+            _ => self.address(value).map(|addr| self.memory.get(addr)),
+        }
+    }
+
+    /// Writes a value, returning its address so that the cache can be invalidated
+    fn store(&mut self, value: Value, val: i64) -> Result<usize, ComputerError> {
+        let addr = self.address(value)?;
+        self.memory.set(addr, val);
+        Ok(addr)
+    }
+
+    fn jump(&mut self, target: i64) -> Result<(), ComputerError> {
+        if target < 0 {
+            return Err(ComputerError::NegativeAddress {
+                address: self.pointer,
+                target,
+            });
+        }
+        self.pointer = target as usize;
+        Ok(())
+    }
+
+    /// Executes the program until it halts, needs input or produces an output
+    pub fn execute(&mut self) -> Result<RunState, ComputerError> {
+        // The cache is set aside, so that the instructions can be borrowed from it
+        let mut cache = std::mem::take(&mut self.cache);
+        let state = self.run(&mut cache);
+        self.cache = cache;
+        state
+    }
+
+    fn run(&mut self, cache: &mut Cache) -> Result<RunState, ComputerError> {
+        let mut uncached = Instruction::Halt;
+        loop {
+            if cache.get(self.pointer).is_none() {
+                let instr = Instruction::parse_instr(&self.memory, self.pointer)?;
+                if let Some(instr) = cache.insert(self.pointer, instr) {
+                    uncached = instr;
+                }
+            }
+            let instr = cache.get(self.pointer).unwrap_or(&uncached);
+            let written = match *instr {
+                Instruction::Add(a, b, c) => {
+                    let val = self.load(a)?.checked_add(self.load(b)?).ok_or(
+                        ComputerError::Overflow {
+                            address: self.pointer,
+                        },
+                    )?;
+                    let addr = self.store(c, val)?;
+                    self.pointer += 4;
+                    Some(addr)
+                }
+                Instruction::Mul(a, b, c) => {
+                    let val = self.load(a)?.checked_mul(self.load(b)?).ok_or(
+                        ComputerError::Overflow {
+                            address: self.pointer,
+                        },
+                    )?;
+                    let addr = self.store(c, val)?;
+                    self.pointer += 4;
+                    Some(addr)
+                }
+                Instruction::Inp(a) => {
+                    self.address(a)?;
+                    let addr = match self.input.pop_front() {
+                        Some(val) => self.store(a, val)?,
+                        None => return Ok(RunState::NeedsInput),
+                    };
+                    self.pointer += 2;
+                    Some(addr)
+                }
+                Instruction::Out(a) => {
+                    let val = self.load(a)?;
+                    self.pointer += 2;
+                    if self.halt_on_output {
+                        return Ok(RunState::ProducedOutput(val));
+                    }
+                    self.output.push_back(val);
+                    None
+                }
+                Instruction::JumpIfTrue(a, b) => {
+                    if self.load(a)? != 0 {
+                        let target = self.load(b)?;
+                        self.jump(target)?;
+                    } else {
+                        self.pointer += 3;
+                    }
+                    None
+                }
+                Instruction::JumpIfFalse(a, b) => {
+                    if self.load(a)? == 0 {
+                        let target = self.load(b)?;
+                        self.jump(target)?;
+                    } else {
+                        self.pointer += 3;
+                    }
+                    None
+                }
+                Instruction::LessThan(a, b, c) => {
+                    let val = (self.load(a)? < self.load(b)?) as i64;
+                    let addr = self.store(c, val)?;
+                    self.pointer += 4;
+                    Some(addr)
+                }
+                Instruction::Equals(a, b, c) => {
+                    let val = (self.load(a)? == self.load(b)?) as i64;
+                    let addr = self.store(c, val)?;
+                    self.pointer += 4;
+                    Some(addr)
+                }
+                Instruction::SetRelativeBase(a) => {
+                    self.relative_base = self.relative_base.checked_add(self.load(a)?).ok_or(
+                        ComputerError::Overflow {
+                            address: self.pointer,
+                        },
+                    )?;
+                    self.pointer += 2;
+                    None
+                }
+                Instruction::Halt => {
+                    self.halted = true;
+                    return Ok(RunState::Halted);
+                }
+                // Only the standard opcodes are decoded
+                Instruction::Extended { .. } => {
+                    return Err(ComputerError::InvalidOpcode {
+                        address: self.pointer,
+                        opcode: self.memory.get(self.pointer),
+                    })
+                }
+            };
+            if let Some(addr) = written {
+                cache.invalidate(addr);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::{FastComputer, Instruction};
+    use crate::intcode_computer::assembler::assemble;
+    use crate::intcode_computer::fixtures::{differential, SELF_PATCHING};
+    use crate::intcode_computer::{parse_input, Computer, ComputerError, RunState, Value};
+
+    #[test]
+    fn fast_matches_computer() {
+        for code in differential() {
+            let mut fast = FastComputer::new(code.clone());
+            let mut computer = Computer::new(code);
+            assert_eq!(fast.execute(), computer.execute());
+            assert_eq!(fast.output, computer.output);
+        }
+
+        let mut fast = FastComputer::new(parse_input("3,0,4,0,42")).halt_on_output();
+        assert_eq!(fast.execute(), Ok(RunState::NeedsInput));
+        fast.input(7);
+        assert_eq!(fast.execute(), Ok(RunState::ProducedOutput(7)));
+        assert_eq!(
+            fast.execute(),
+            Err(ComputerError::InvalidOpcode {
+                address: 4,
+                opcode: 42
+            })
+        );
+    }
+
+    #[test]
+    fn fast_cache_invalidation() {
+        // Each patch of the output instruction drops it from the cache, to be decoded again
+        let mut fast = FastComputer::new(assemble(SELF_PATCHING).unwrap()).halt_on_output();
+        for value in 1..=3 {
+            assert_eq!(fast.execute(), Ok(RunState::ProducedOutput(value)));
+            assert_eq!(
+                fast.cache.get(0),
+                Some(&Instruction::Out(Value::Immediate(value)))
+            );
+        }
+        assert_eq!(fast.execute(), Ok(RunState::Halted));
+        assert_eq!(fast.cache.get(0), None);
+    }
+}
@@ -0,0 +1,42 @@
+use super::assembler::assemble;
+use super::parse_input;
+
+/// Outputs a copy of itself (day 9 example)
+pub const QUINE: &str = "109,1,204,-1,1001,100,1,100,1008,100,16,101,1006,101,0,99";
+
+/// Adds up its inputs forever, outputting the running total
+pub const RUNNING_SUM: &str = "109,1000,203,0,22201,0,1,1,204,1,1105,1,2";
+
+/// Outputs 2 then 1, counting down in the cell at address 14
+pub const COUNTDOWN: &str = "
+            ADD #2, #0, [counter]
+    loop:   OUT [counter]
+            ADD [counter], #-1, [counter]
+            JT [counter], #loop
+            HLT
+    counter: DB 0
+";
+
+/// Outputs 1, 2 and 3 by incrementing the operand of its own output instruction
+pub const SELF_PATCHING: &str = "
+    start:  OUT #1
+            ADD [1], #1, [1]
+            LT [1], #4, [tmp]
+            JT [tmp], #start
+            HLT
+    tmp:    DB 0
+";
+
+/// Programs stopping on an overflow at address 2 : of the relative base,
+/// then of a relative address
+pub const RELATIVE_OVERFLOWS: [&str; 2] = [
+    "109,9223372036854775807,109,1,99",
+    "109,1,204,9223372036854775807,99",
+];
+
+/// Programs every interpreter must run as `Computer` does
+pub fn differential() -> Vec<Vec<i64>> {
+    let mut programs = vec![parse_input(QUINE), assemble(SELF_PATCHING).unwrap()];
+    programs.extend(RELATIVE_OVERFLOWS.iter().map(|code| parse_input(code)));
+    programs
+}
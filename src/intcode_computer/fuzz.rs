@@ -0,0 +1,455 @@
+use super::{Computer, ComputerError, Instruction, RunState, Value};
+use std::convert::TryFrom;
+use std::panic::{catch_unwind, AssertUnwindSafe};
+
+/// Number of instructions of a generated program, before its final `Halt`
+const PROGRAM_LEN: usize = 24;
+/// Number of data cells following the code of a generated program
+const DATA_LEN: usize = 64;
+/// Maximum number of instructions executed by a run, before it is reported as a hang
+const MAX_CYCLES: u64 = 100_000;
+
+/// Pseudo-random number generator (xorshift64*), so that any run can be replayed from its seed
+#[derive(Debug, Clone)]
+pub struct Rng(u64);
+
+impl Rng {
+    pub fn new(seed: u64) -> Self {
+        // The state of a xorshift generator must not be 0
+        Rng(seed ^ 0x9e37_79b9_7f4a_7c15)
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.0 ^= self.0 >> 12;
+        self.0 ^= self.0 << 25;
+        self.0 ^= self.0 >> 27;
+        self.0.wrapping_mul(0x2545_f491_4f6c_dd1d)
+    }
+
+    /// Random number in `0..n`
+    fn below(&mut self, n: usize) -> usize {
+        (self.next_u64() % n as u64) as usize
+    }
+
+    /// Random number in `from..=to`
+    fn between(&mut self, from: i64, to: i64) -> i64 {
+        from + self.below((to - from + 1) as usize) as i64
+    }
+}
+
+/// Instructions and parameter modes used by the generated programs
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Dialect {
+    /// `Add`, `Mul` and `Halt` in position mode, run by both `Computer` and `reference`
+    Day2,
+    /// Every instruction and parameter mode, run by `Computer` alone
+    Full,
+}
+
+/// Generated program
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Case {
+    pub code: Vec<i64>,
+    /// Input of the program, one value per `Inp` instruction
+    pub inputs: Vec<i64>,
+    /// Generated instructions, with their address
+    pub instructions: Vec<(usize, Instruction)>,
+}
+
+/// Opcode of an instruction, with the parameter modes of its operands
+fn encode(instr: &Instruction) -> Vec<i64> {
+    let mut cells = vec![instr.opcode()];
+    let mut factor = 100;
+    for operand in instr.operands() {
+        let (mode, value) = match operand {
+            Value::Position(addr) => (0, *addr as i64),
+            Value::Immediate(x) => (1, *x),
+            Value::Relative(offset) => (2, *offset),
+        };
+        cells[0] += mode * factor;
+        factor *= 10;
+        cells.push(value);
+    }
+    cells
+}
+
+/// Program being generated. Its code is followed by `DATA_LEN` data cells
+struct Generator<'a> {
+    rng: &'a mut Rng,
+    dialect: Dialect,
+    /// Address of the first data cell
+    data: usize,
+}
+
+impl Generator<'_> {
+    /// Operand read by an instruction
+    fn read(&mut self) -> Value {
+        match (self.dialect, self.rng.below(3)) {
+            // The day 2 programs read their code as well as their data
+            (Dialect::Day2, _) => Value::Position(self.rng.below(self.data + DATA_LEN)),
+            (Dialect::Full, 0) => Value::Immediate(self.rng.between(-20, 20)),
+            _ => self.write(),
+        }
+    }
+
+    /// Operand written by an instruction, inside the data cells
+    fn write(&mut self) -> Value {
+        match (self.dialect, self.rng.below(2)) {
+            (Dialect::Full, 0) => Value::Relative(self.rng.between(0, 15)),
+            _ => Value::Position(self.data + self.rng.below(DATA_LEN)),
+        }
+    }
+
+    /// Random instruction. Jumps only go forward, to one of the following addresses
+    fn instruction(&mut self, targets: &[usize]) -> Instruction {
+        let opcodes = match self.dialect {
+            Dialect::Day2 => 2,
+            Dialect::Full => 9,
+        };
+        match self.rng.below(opcodes) {
+            0 => Instruction::Add(self.read(), self.read(), self.write()),
+            1 => Instruction::Mul(self.read(), self.read(), self.write()),
+            2 => Instruction::Inp(self.write()),
+            3 => Instruction::Out(self.read()),
+            n @ 4..=5 => {
+                let condition = self.read();
+                let target = Value::Immediate(targets[self.rng.below(targets.len())] as i64);
+                match n {
+                    4 => Instruction::JumpIfTrue(condition, target),
+                    _ => Instruction::JumpIfFalse(condition, target),
+                }
+            }
+            6 => Instruction::LessThan(self.read(), self.read(), self.write()),
+            7 => Instruction::Equals(self.read(), self.read(), self.write()),
+            _ => Instruction::SetRelativeBase(Value::Immediate(self.rng.between(-4, 4))),
+        }
+    }
+}
+
+/// Generates a random program of the dialect, made of valid instructions and parameter modes.
+/// It writes only to its data cells, so that it always reaches its final `Halt`, unless the
+/// relative base drifts into its code
+pub fn generate(rng: &mut Rng, dialect: Dialect) -> Case {
+    // The instructions are drawn first, to know the addresses jumps can target.
+    // Their operands do not change their size
+    let mut kinds: Vec<Instruction> = vec![];
+    let mut generator = Generator {
+        rng,
+        dialect,
+        data: 0,
+    };
+    if dialect == Dialect::Full {
+        kinds.push(Instruction::SetRelativeBase(Value::Immediate(0)));
+    }
+    while kinds.len() < PROGRAM_LEN {
+        kinds.push(generator.instruction(&[0]));
+    }
+    kinds.push(Instruction::Halt);
+    let mut addresses = vec![0];
+    for instr in kinds.iter() {
+        addresses.push(addresses.last().unwrap() + instr.args_count() + 1);
+    }
+    generator.data = addresses.pop().unwrap();
+
+    let mut instructions = vec![];
+    for (idx, kind) in kinds.iter().enumerate() {
+        let instr = match kind {
+            // The relative base starts in the middle of the data cells
+            Instruction::SetRelativeBase(_) if idx == 0 => Instruction::SetRelativeBase(
+                Value::Immediate((generator.data + DATA_LEN / 3) as i64),
+            ),
+            Instruction::Halt => Instruction::Halt,
+            _ => loop {
+                let instr = generator.instruction(&addresses[idx + 1..]);
+                if std::mem::discriminant(&instr) == std::mem::discriminant(kind) {
+                    break instr;
+                }
+            },
+        };
+        instructions.push((addresses[idx], instr));
+    }
+
+    let mut code: Vec<i64> = instructions
+        .iter()
+        .flat_map(|(_, instr)| encode(instr))
+        .collect();
+    let data_max = match dialect {
+        Dialect::Day2 => 50,
+        Dialect::Full => 20,
+    };
+    let rng = generator.rng;
+    code.extend((0..DATA_LEN).map(|_| rng.between(0, data_max)));
+    let inputs = instructions
+        .iter()
+        .filter(|(_, instr)| matches!(instr, Instruction::Inp(_)))
+        .map(|_| rng.between(-20, 20))
+        .collect();
+    Case {
+        code,
+        inputs,
+        instructions,
+    }
+}
+
+/// How an interpreter stopped running a day 2 program
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Outcome {
+    /// Halted, with the final memory of the program
+    Halted(Vec<i64>),
+    /// Stopped on an error, or waiting for input
+    Failed,
+    /// A result does not fit in an i64
+    Overflow,
+}
+
+/// Problem found by running a program
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Finding {
+    /// The generated instruction at the address is decoded differently
+    Decoding {
+        address: usize,
+        expected: Instruction,
+        decoded: Result<Instruction, ComputerError>,
+    },
+    /// An interpreter panicked
+    Panic {
+        interpreter: &'static str,
+        message: String,
+    },
+    /// The program did not stop within the cycle budget
+    Hang,
+    /// `Computer::halted` disagrees with the state the execution returned
+    HaltedMismatch {
+        state: Result<RunState, ComputerError>,
+        halted: bool,
+    },
+    /// The interpreters do not stop the same way, or on a different memory
+    Divergence {
+        computer: Outcome,
+        reference: Outcome,
+    },
+}
+
+impl std::fmt::Display for Finding {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Finding::Decoding {
+                address,
+                expected,
+                decoded,
+            } => write!(
+                f,
+                "instruction {} at address {} decoded as {:?}",
+                expected, address, decoded
+            ),
+            Finding::Panic {
+                interpreter,
+                message,
+            } => write!(f, "{} panicked: {}", interpreter, message),
+            Finding::Hang => write!(f, "no halt after {} instructions", MAX_CYCLES),
+            Finding::HaltedMismatch { state, halted } => {
+                write!(f, "execution returned {:?}, halted() is {}", state, halted)
+            }
+            Finding::Divergence {
+                computer,
+                reference,
+            } => write!(
+                f,
+                "computer: {:?}, day 2 reference: {:?}",
+                computer, reference
+            ),
+        }
+    }
+}
+
+fn panic_message(payload: Box<dyn std::any::Any + Send>) -> String {
+    match payload.downcast::<String>() {
+        Ok(message) => *message,
+        Err(payload) => match payload.downcast::<&str>() {
+            Ok(message) => message.to_string(),
+            Err(_) => "unknown panic".to_string(),
+        },
+    }
+}
+
+/// Runs a program on `Computer`
+fn run_computer(case: &Case, findings: &mut Vec<Finding>) -> Option<Outcome> {
+    let run = catch_unwind(AssertUnwindSafe(|| {
+        let mut computer = Computer::new(case.code.clone()).with_cycle_budget(MAX_CYCLES);
+        for input in case.inputs.iter() {
+            computer.input(*input);
+        }
+        let state = computer.execute();
+        let outcome = match state {
+            Ok(RunState::Halted) => Outcome::Halted(
+                (0..case.code.len())
+                    .map(|addr| computer.get(addr))
+                    .collect(),
+            ),
+            Err(ComputerError::Overflow { .. }) => Outcome::Overflow,
+            _ => Outcome::Failed,
+        };
+        let mismatch = (computer.halted() != (state == Ok(RunState::Halted))).then(|| {
+            Finding::HaltedMismatch {
+                state: state.clone(),
+                halted: computer.halted(),
+            }
+        });
+        (
+            outcome,
+            mismatch.or((state == Ok(RunState::BudgetExhausted)).then_some(Finding::Hang)),
+        )
+    }));
+    match run {
+        Ok((outcome, finding)) => {
+            findings.extend(finding);
+            Some(outcome)
+        }
+        Err(payload) => {
+            findings.push(Finding::Panic {
+                interpreter: "computer",
+                message: panic_message(payload),
+            });
+            None
+        }
+    }
+}
+
+/// Runs a day 2 program on a standalone interpreter of `Add`, `Mul` and `Halt`, which shares
+/// no decoding nor execution code with `Computer`. As for `Computer`, reads past the end
+/// of the memory give 0 and writes grow it
+pub fn reference(code: &[i64]) -> Outcome {
+    let mut memory = code.to_vec();
+    let mut pointer = 0;
+    let cell = |memory: &[i64], addr: i64| -> Option<i64> {
+        usize::try_from(addr)
+            .ok()
+            .map(|addr| memory.get(addr).cloned().unwrap_or(0))
+    };
+    loop {
+        let opcode = match memory.get(pointer) {
+            Some(opcode) => *opcode,
+            None => return Outcome::Failed,
+        };
+        if opcode == 99 {
+            memory.truncate(code.len());
+            return Outcome::Halted(memory);
+        }
+        if opcode != 1 && opcode != 2 {
+            return Outcome::Failed;
+        }
+        let params: Vec<i64> = (1..4)
+            .map(|offset| memory.get(pointer + offset).cloned().unwrap_or(0))
+            .collect();
+        let (a, b) = match (cell(&memory, params[0]), cell(&memory, params[1])) {
+            (Some(a), Some(b)) => (a, b),
+            _ => return Outcome::Failed,
+        };
+        let result = if opcode == 1 {
+            a.checked_add(b)
+        } else {
+            a.checked_mul(b)
+        };
+        let (result, dst) = match (result, usize::try_from(params[2])) {
+            (None, _) => return Outcome::Overflow,
+            (Some(_), Err(_)) => return Outcome::Failed,
+            (Some(result), Ok(dst)) => (result, dst),
+        };
+        if dst >= memory.len() {
+            memory.resize(dst + 1, 0);
+        }
+        memory[dst] = result;
+        pointer += 4;
+    }
+}
+
+/// Runs a program, reporting what went wrong. Day 2 programs are also run on `reference`,
+/// and compared with `Computer`
+pub fn check(case: &Case, dialect: Dialect) -> Vec<Finding> {
+    let mut findings = vec![];
+    let memory = super::Memory::new(case.code.clone());
+    for (address, expected) in case.instructions.iter() {
+        let decoded = Instruction::parse_instr(&memory, *address);
+        if decoded.as_ref() != Ok(expected) {
+            findings.push(Finding::Decoding {
+                address: *address,
+                expected: expected.clone(),
+                decoded,
+            });
+        }
+    }
+
+    let computer = run_computer(case, &mut findings);
+    if dialect == Dialect::Day2 {
+        let reference = reference(&case.code);
+        match computer {
+            Some(computer) if computer != reference => findings.push(Finding::Divergence {
+                computer,
+                reference,
+            }),
+            _ => {}
+        }
+    }
+    findings
+}
+
+/// Program for which a problem was found
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Failure {
+    /// Seed generating the program
+    pub seed: u64,
+    pub case: Case,
+    pub findings: Vec<Finding>,
+}
+
+/// Generates and checks programs of the dialect. The seed of each program is drawn from `seed`
+pub fn fuzz(seed: u64, runs: usize, dialect: Dialect) -> Vec<Failure> {
+    let mut seeds = Rng::new(seed);
+    (0..runs)
+        .filter_map(|_| {
+            let seed = seeds.next_u64();
+            let case = generate(&mut Rng::new(seed), dialect);
+            let findings = check(&case, dialect);
+            (!findings.is_empty()).then_some(Failure {
+                seed,
+                case,
+                findings,
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::{check, fuzz, generate, reference, Case, Dialect, Outcome, Rng};
+
+    #[test]
+    fn fuzz_campaigns() {
+        assert_eq!(fuzz(2019, 500, Dialect::Day2), vec![]);
+        assert_eq!(fuzz(2019, 500, Dialect::Full), vec![]);
+        let case = generate(&mut Rng::new(7), Dialect::Day2);
+        assert_eq!(case, generate(&mut Rng::new(7), Dialect::Day2));
+        assert!(case.instructions.len() > 1);
+
+        // Ends on a `Halt` without any cell after it
+        let case = Case {
+            code: vec![1, 0, 0, 0, 99],
+            inputs: vec![],
+            instructions: vec![],
+        };
+        assert_eq!(check(&case, Dialect::Day2), vec![]);
+
+        assert_eq!(
+            reference(&[1, 9, 10, 3, 2, 3, 11, 0, 99, 30, 40, 50]),
+            Outcome::Halted(vec![3500, 9, 10, 70, 2, 3, 11, 0, 99, 30, 40, 50])
+        );
+        // Both interpreters stop on the overflow
+        let case = Case {
+            code: vec![1, 5, 5, 0, 99, i64::MAX],
+            inputs: vec![],
+            instructions: vec![],
+        };
+        assert_eq!(reference(&case.code), Outcome::Overflow);
+        assert_eq!(check(&case, Dialect::Day2), vec![]);
+    }
+}
@@ -0,0 +1,446 @@
+use super::{Computer, ComputerError, Instruction, Memory, RunState, Value};
+use std::collections::BTreeMap;
+use std::sync::Arc;
+
+/// Opcodes of the day 9 computer, with their mnemonic and number of operands.
+/// The table only describes them : they are decoded into their own `Instruction` variants,
+/// and executed by the computer itself rather than through `Semantics`
+const STANDARD: [(i64, &str, usize); 10] = [
+    (1, "ADD", 3),
+    (2, "MUL", 3),
+    (3, "INP", 1),
+    (4, "OUT", 1),
+    (5, "JT", 2),
+    (6, "JF", 2),
+    (7, "LT", 3),
+    (8, "EQ", 3),
+    (9, "ARB", 1),
+    (99, "HLT", 0),
+];
+
+/// Mnemonic and number of operands of the standard opcodes, indexed by opcode
+const BY_OPCODE: [Option<(&str, usize)>; 100] = {
+    let mut table = [None; 100];
+    let mut i = 0;
+    while i < STANDARD.len() {
+        let (opcode, mnemonic, args) = STANDARD[i];
+        table[opcode as usize] = Some((mnemonic, args));
+        i += 1;
+    }
+    table
+};
+
+/// Mnemonic and number of operands of a standard opcode
+pub(super) fn standard_opcode(opcode: i64) -> Option<(&'static str, usize)> {
+    if (0..100).contains(&opcode) {
+        BY_OPCODE[opcode as usize]
+    } else {
+        None
+    }
+}
+
+/// Error registering an opcode that does not fit in the two lowest digits of an instruction
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InvalidOpcodeError {
+    pub opcode: i64,
+}
+
+impl std::fmt::Display for InvalidOpcodeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "invalid opcode {}", self.opcode)
+    }
+}
+
+impl std::error::Error for InvalidOpcodeError {}
+
+/// Behaviour of a registered opcode. Returns the reason why the computer should stop, if any
+pub type Semantics =
+    Arc<dyn Fn(&mut Context) -> Result<Option<RunState>, ComputerError> + Send + Sync>;
+
+/// Opcode of an instruction set
+#[derive(Clone)]
+pub struct Opcode {
+    pub mnemonic: &'static str,
+    /// Number of operands following the opcode
+    pub args: usize,
+    /// None for the standard opcodes, executed by the computer itself
+    semantics: Option<Semantics>,
+}
+
+impl Opcode {
+    /// Tells whether the opcode is one of the standard set, with its usual behaviour
+    pub fn is_standard(&self) -> bool {
+        self.semantics.is_none()
+    }
+}
+
+/// Opcodes are equal when they share their semantics
+impl PartialEq for Opcode {
+    fn eq(&self, other: &Self) -> bool {
+        let same_semantics = match (&self.semantics, &other.semantics) {
+            (Some(a), Some(b)) => Arc::ptr_eq(a, b),
+            (a, b) => a.is_none() && b.is_none(),
+        };
+        self.mnemonic == other.mnemonic && self.args == other.args && same_semantics
+    }
+}
+
+impl Eq for Opcode {}
+
+/// Opcodes known to a computer, by their value in the two lowest digits of an instruction.
+/// Instructions of the registered opcodes are decoded as `Instruction::Extended`
+#[derive(Clone, PartialEq, Eq)]
+pub struct InstructionSet {
+    opcodes: BTreeMap<i64, Opcode>,
+}
+
+impl std::fmt::Debug for InstructionSet {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.debug_map()
+            .entries(
+                self.opcodes
+                    .iter()
+                    .map(|(op, opcode)| (op, &opcode.mnemonic)),
+            )
+            .finish()
+    }
+}
+
+impl Default for InstructionSet {
+    fn default() -> Self {
+        InstructionSet::standard()
+    }
+}
+
+impl InstructionSet {
+    /// Opcodes of the day 9 computer
+    pub fn standard() -> Self {
+        let opcodes = STANDARD
+            .iter()
+            .map(|(op, mnemonic, args)| {
+                let opcode = Opcode {
+                    mnemonic,
+                    args: *args,
+                    semantics: None,
+                };
+                (*op, opcode)
+            })
+            .collect();
+        InstructionSet { opcodes }
+    }
+
+    /// Registers an opcode, replacing the one with the same value if any.
+    /// The opcode must fit in the two lowest digits of an instruction
+    pub fn register(
+        mut self,
+        opcode: i64,
+        mnemonic: &'static str,
+        args: usize,
+        semantics: impl Fn(&mut Context) -> Result<Option<RunState>, ComputerError>
+            + Send
+            + Sync
+            + 'static,
+    ) -> Result<Self, InvalidOpcodeError> {
+        if !(1..100).contains(&opcode) {
+            return Err(InvalidOpcodeError { opcode });
+        }
+        let opcode_def = Opcode {
+            mnemonic,
+            args,
+            semantics: Some(Arc::new(semantics)),
+        };
+        self.opcodes.insert(opcode, opcode_def);
+        Ok(self)
+    }
+
+    pub fn opcode(&self, opcode: i64) -> Option<&Opcode> {
+        self.opcodes.get(&opcode)
+    }
+
+    pub fn opcodes(&self) -> impl Iterator<Item = (i64, &Opcode)> {
+        self.opcodes.iter().map(|(op, opcode)| (*op, opcode))
+    }
+
+    /// Opcode of the given mnemonic, case insensitive
+    pub fn by_mnemonic(&self, mnemonic: &str) -> Option<(i64, &Opcode)> {
+        self.opcodes()
+            .find(|(_, opcode)| opcode.mnemonic.eq_ignore_ascii_case(mnemonic))
+    }
+
+    /// Decodes the instruction at the given address
+    pub fn decode(&self, code: &Memory, pointer: usize) -> Result<Instruction, ComputerError> {
+        if !code.contains(pointer) {
+            return Err(ComputerError::PointerOutOfBounds { pointer });
+        }
+        let cell = code.get(pointer);
+        match self.opcodes.get(&(cell % 100)) {
+            Some(opcode) if !opcode.is_standard() => {
+                let operands = (1..=opcode.args)
+                    .map(|offset| {
+                        let val = code.get(pointer + offset);
+                        Instruction::operand(cell, offset, opcode.args, val, pointer)
+                    })
+                    .collect::<Result<_, _>>()?;
+                Ok(Instruction::Extended {
+                    opcode: cell % 100,
+                    mnemonic: opcode.mnemonic,
+                    operands,
+                })
+            }
+            _ => Instruction::parse_instr(code, pointer),
+        }
+    }
+}
+
+/// Side effects of the last extended instruction, recorded for the traces
+#[derive(Debug, Clone, Default)]
+pub(super) struct Effects {
+    /// Addresses written, in order
+    pub writes: Vec<usize>,
+    pub inputs: Vec<i64>,
+    pub outputs: Vec<i64>,
+}
+
+/// Access to the computer executing a registered instruction, with its decoded operands
+pub struct Context<'a> {
+    computer: &'a mut Computer,
+    operands: &'a [Value],
+    /// Address of the instruction
+    address: usize,
+    /// Whether the instruction moved the code pointer
+    jumped: bool,
+}
+
+impl Context<'_> {
+    /// Address of the instruction
+    pub fn address(&self) -> usize {
+        self.address
+    }
+
+    pub fn operands(&self) -> &[Value] {
+        self.operands
+    }
+
+    /// The nth operand, if the instruction has one
+    fn operand(&self, n: usize) -> Result<Value, ComputerError> {
+        self.operands
+            .get(n)
+            .cloned()
+            .ok_or_else(|| self.error(&format!("no operand {}", n)))
+    }
+
+    /// Reads the value of the nth operand
+    pub fn read(&self, n: usize) -> Result<i64, ComputerError> {
+        self.computer.read(&self.operand(n)?)
+    }
+
+    /// Writes a value to the address targeted by the nth operand
+    pub fn write(&mut self, n: usize, value: i64) -> Result<(), ComputerError> {
+        let operand = self.operand(n)?;
+        let addr = self.computer.address(&operand)?;
+        self.computer.write(&operand, value)?;
+        self.computer.effects.writes.push(addr);
+        Ok(())
+    }
+
+    pub fn get(&self, addr: usize) -> i64 {
+        self.computer.get(addr)
+    }
+
+    pub fn set(&mut self, addr: usize, value: i64) {
+        self.computer.set(addr, value);
+        self.computer.effects.writes.push(addr);
+    }
+
+    /// Takes the next input, from the input queue or the input port.
+    /// Without input, the instruction should return `RunState::NeedsInput`
+    pub fn input(&mut self) -> Option<i64> {
+        let input = self.computer.next_input();
+        self.computer.effects.inputs.extend(input);
+        input
+    }
+
+    /// Produces an output. The instruction should return the state given back, which
+    /// stops the computer when it halts on output
+    pub fn output(&mut self, value: i64) -> Option<RunState> {
+        self.computer.effects.outputs.push(value);
+        if self.computer.halt_on_output {
+            return Some(RunState::ProducedOutput(value));
+        }
+        self.computer.emit(value);
+        None
+    }
+
+    /// Moves the code pointer, instead of going to the next instruction
+    pub fn jump(&mut self, target: i64) -> Result<(), ComputerError> {
+        self.computer.pointer = self.computer.jump_target(target)?;
+        self.jumped = true;
+        Ok(())
+    }
+
+    pub fn relative_base(&self) -> i64 {
+        self.computer.relative_base
+    }
+
+    pub fn set_relative_base(&mut self, relative_base: i64) {
+        self.computer.relative_base = relative_base;
+    }
+
+    /// Error to return when the instruction cannot be executed
+    pub fn error(&self, message: &str) -> ComputerError {
+        ComputerError::Extension {
+            address: self.address,
+            message: message.to_string(),
+        }
+    }
+}
+
+impl Computer {
+    /// Executes an instruction of a registered opcode, decoded at the code pointer
+    pub(super) fn execute_extended(
+        &mut self,
+        opcode: i64,
+        operands: &[Value],
+    ) -> Result<Option<RunState>, ComputerError> {
+        let pointer = self.pointer;
+        let semantics = self
+            .instruction_set
+            .as_ref()
+            .and_then(|set| set.opcodes.get(&opcode))
+            .and_then(|opcode| opcode.semantics.clone())
+            .ok_or(ComputerError::InvalidOpcode {
+                address: pointer,
+                opcode: self.code.get(pointer),
+            })?;
+        self.effects = Effects::default();
+        let mut context = Context {
+            computer: self,
+            operands,
+            address: pointer,
+            jumped: false,
+        };
+        let state = semantics(&mut context);
+        let jumped = context.jumped;
+        match state {
+            Ok(Some(RunState::NeedsInput)) => {}
+            Ok(Some(RunState::Halted)) => self.halted = true,
+            Ok(_) if !jumped => self.pointer = pointer + operands.len() + 1,
+            _ => {}
+        }
+        state
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::{InstructionSet, InvalidOpcodeError};
+    use crate::intcode_computer::assembler::assemble_with;
+    use crate::intcode_computer::disassembler::disassemble_with;
+    use crate::intcode_computer::trace::{replay_with, Trace};
+    use crate::intcode_computer::{parse_input, Computer, ComputerError, RunState};
+
+    #[test]
+    fn instruction_set_extensions() {
+        let set = InstructionSet::standard()
+            .register(10, "DIV", 3, |ctx| {
+                let (a, b) = (ctx.read(0)?, ctx.read(1)?);
+                let val = a
+                    .checked_div(b)
+                    .ok_or_else(|| ctx.error("division by zero"))?;
+                ctx.write(2, val)?;
+                Ok(None)
+            })
+            .unwrap()
+            .register(11, "MOD", 3, |ctx| {
+                let (a, b) = (ctx.read(0)?, ctx.read(1)?);
+                let val = a
+                    .checked_rem_euclid(b)
+                    .ok_or_else(|| ctx.error("division by zero"))?;
+                ctx.write(2, val)?;
+                Ok(None)
+            })
+            .unwrap()
+            // System call 1 outputs the zero-terminated string at the given address
+            .register(12, "SYS", 2, |ctx| match ctx.read(0)? {
+                1 => {
+                    let mut addr = ctx.read(1)? as usize;
+                    while ctx.get(addr) != 0 {
+                        ctx.output(ctx.get(addr));
+                        addr += 1;
+                    }
+                    Ok(None)
+                }
+                _ => Err(ctx.error("unknown system call")),
+            })
+            .unwrap();
+        assert_eq!(set.opcode(10).unwrap().mnemonic, "DIV");
+        assert!(set.opcode(99).unwrap().is_standard());
+
+        let code =
+            parse_input("1110,17,5,20,1111,17,5,21,4,20,4,21,1112,1,22,99,0,0,0,0,0,0,104,105,0");
+        let mut computer = Computer::new(code.clone())
+            .record_trace()
+            .record_profile()
+            .record_coverage()
+            .with_instruction_set(set.clone());
+        assert_eq!(
+            computer.current_instruction().map(|i| i.to_string()),
+            Ok("DIV #17, #5, [20]".to_string())
+        );
+        assert_eq!(computer.execute(), Ok(RunState::Halted));
+        assert_eq!(computer.output, vec![3, 2, 104, 105]);
+        assert_eq!(computer.profile().unwrap().opcodes.get("SYS"), Some(&1));
+        let summary = computer.coverage().unwrap().summary(&code);
+        assert_eq!((summary.executed, summary.instructions), (6, 6));
+
+        // The registered opcodes are traced, and replayed
+        let trace = computer.take_trace().unwrap();
+        let sys = &trace.entries()[4];
+        assert_eq!(sys.to_string(), "12: SYS #1, #22 | 1,22 | - | - | 104,105");
+        assert_eq!(
+            Trace::parse_with(&trace.to_string(), &set),
+            Ok(trace.clone())
+        );
+        let replayed = Computer::new(code.clone()).with_instruction_set(set.clone());
+        assert_eq!(replay_with(replayed, &trace), Ok(None));
+
+        // And disassembled, then assembled back
+        let listing = disassemble_with(&code, &set);
+        assert!(listing.is_code(4) && listing.is_code(12));
+        assert_eq!(assemble_with(&listing.to_string(), &set), Ok(code.clone()));
+        assert_eq!(
+            Computer::new(code).execute(),
+            Err(ComputerError::InvalidOpcode {
+                address: 0,
+                opcode: 1110
+            })
+        );
+
+        for opcode in &[1110, 1111] {
+            let mut computer =
+                Computer::new(vec![*opcode, 1, 0, 5, 99]).with_instruction_set(set.clone());
+            assert_eq!(
+                computer.execute(),
+                Err(ComputerError::Extension {
+                    address: 0,
+                    message: "division by zero".to_string()
+                })
+            );
+        }
+
+        // Out of range opcodes are rejected, missing operands are errors
+        assert_eq!(
+            set.clone().register(100, "BIG", 0, |_| Ok(None)).err(),
+            Some(InvalidOpcodeError { opcode: 100 })
+        );
+        let set = set.register(13, "BAD", 1, |ctx| ctx.read(1).map(|_| None));
+        let mut computer = Computer::new(vec![13, 0, 99]).with_instruction_set(set.unwrap());
+        assert_eq!(
+            computer.execute(),
+            Err(ComputerError::Extension {
+                address: 0,
+                message: "no operand 1".to_string()
+            })
+        );
+    }
+}
@@ -0,0 +1,708 @@
+use super::{Computer, ComputerError, Instruction, Memory, RunState, Value};
+use std::collections::{BTreeMap, BTreeSet, VecDeque};
+
+/// Arithmetic and comparison operations
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BinOp {
+    Add,
+    Mul,
+    LessThan,
+    Equals,
+}
+
+impl BinOp {
+    /// Applies the operation, None on overflow
+    pub(super) fn apply(self, a: i64, b: i64) -> Option<i64> {
+        match self {
+            BinOp::Add => a.checked_add(b),
+            BinOp::Mul => a.checked_mul(b),
+            BinOp::LessThan => Some((a < b) as i64),
+            BinOp::Equals => Some((a == b) as i64),
+        }
+    }
+
+    pub(super) fn symbol(self) -> &'static str {
+        match self {
+            BinOp::Add => "+",
+            BinOp::Mul => "*",
+            BinOp::LessThan => "<",
+            BinOp::Equals => "==",
+        }
+    }
+}
+
+/// Operation of a basic block. The operands keep the addressing modes of Intcode,
+/// memory cells acting as registers
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Op {
+    Copy {
+        dst: Value,
+        src: Value,
+    },
+    Binary {
+        op: BinOp,
+        dst: Value,
+        a: Value,
+        b: Value,
+    },
+    Input {
+        dst: Value,
+    },
+    Output {
+        src: Value,
+    },
+    AdjustBase {
+        delta: Value,
+    },
+}
+
+impl Op {
+    /// Number of cells of the instruction the operation comes from
+    fn len(&self) -> usize {
+        match self {
+            Op::Copy { .. } | Op::Binary { .. } => 4,
+            Op::Input { .. } | Op::Output { .. } | Op::AdjustBase { .. } => 2,
+        }
+    }
+
+    /// Lifts an arithmetic instruction, folding its constant operands
+    fn lift(op: BinOp, a: Value, b: Value, dst: Value) -> Self {
+        match (op, a, b) {
+            (_, Value::Immediate(x), Value::Immediate(y)) if op.apply(x, y).is_some() => Op::Copy {
+                dst,
+                src: Value::Immediate(op.apply(x, y).unwrap()),
+            },
+            (BinOp::Add, src, Value::Immediate(0)) | (BinOp::Add, Value::Immediate(0), src) => {
+                Op::Copy { dst, src }
+            }
+            (BinOp::Mul, src, Value::Immediate(1)) | (BinOp::Mul, Value::Immediate(1), src) => {
+                Op::Copy { dst, src }
+            }
+            // Reading a relative operand can fail, so it is kept
+            (BinOp::Mul, x, Value::Immediate(0)) | (BinOp::Mul, Value::Immediate(0), x)
+                if !matches!(x, Value::Relative(_)) =>
+            {
+                Op::Copy {
+                    dst,
+                    src: Value::Immediate(0),
+                }
+            }
+            _ => Op::Binary { op, dst, a, b },
+        }
+    }
+}
+
+impl std::fmt::Display for Op {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Op::Copy { dst, src } => write!(f, "{} = {}", dst, src),
+            Op::Binary { op, dst, a, b } => write!(f, "{} = {} {} {}", dst, a, op.symbol(), b),
+            Op::Input { dst } => write!(f, "{} = input", dst),
+            Op::Output { src } => write!(f, "output {}", src),
+            Op::AdjustBase { delta } => write!(f, "rb += {}", delta),
+        }
+    }
+}
+
+/// How a basic block ends
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Terminator {
+    Jump(usize),
+    /// Jumps to `target` if `cond` is zero (or non zero), else continues at `next`
+    Branch {
+        cond: Value,
+        if_zero: bool,
+        target: Value,
+        next: usize,
+    },
+    /// Jump to a function, after storing the return address on the stack
+    Call {
+        target: usize,
+        returns_to: usize,
+    },
+    /// Jump to a return address stored on the stack
+    Return {
+        target: Value,
+    },
+    /// Jump to an address read from memory
+    Computed {
+        target: Value,
+    },
+    Halt,
+    /// The instruction at the end of the block is left to the plain interpreter,
+    /// as it cannot be lifted (invalid instruction, write to an immediate operand...)
+    Interpret,
+}
+
+/// Sequence of operations executed together, from an entry address
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Block {
+    pub start: usize,
+    /// Operations, with the address of the instruction they come from
+    pub ops: Vec<(usize, Op)>,
+    pub terminator: Terminator,
+    /// Address of the instruction ending the block
+    pub exit: usize,
+    /// Addresses of the jumps never taken, left out of the operations
+    pub skipped: Vec<usize>,
+}
+
+impl Block {
+    /// Lifts the instructions from the given address, up to the first jump
+    fn lift(memory: &Memory, start: usize) -> Self {
+        let mut ops = vec![];
+        let mut skipped = vec![];
+        let mut addr = start;
+        let terminator = loop {
+            let instr = match Instruction::parse_instr(memory, addr) {
+                Ok(instr) => instr,
+                Err(_) => break Terminator::Interpret,
+            };
+            if let Some(Value::Immediate(_)) = instr.destination() {
+                break Terminator::Interpret;
+            }
+            let next = addr + instr.args_count() + 1;
+            let op = match instr {
+                Instruction::Add(a, b, c) => Op::lift(BinOp::Add, a, b, c),
+                Instruction::Mul(a, b, c) => Op::lift(BinOp::Mul, a, b, c),
+                Instruction::LessThan(a, b, c) => Op::lift(BinOp::LessThan, a, b, c),
+                Instruction::Equals(a, b, c) => Op::lift(BinOp::Equals, a, b, c),
+                Instruction::Inp(dst) => Op::Input { dst },
+                Instruction::Out(src) => Op::Output { src },
+                Instruction::SetRelativeBase(delta) => Op::AdjustBase { delta },
+                Instruction::Halt => break Terminator::Halt,
+                Instruction::Extended { .. } => break Terminator::Interpret,
+                Instruction::JumpIfTrue(cond, target) | Instruction::JumpIfFalse(cond, target) => {
+                    let if_zero = matches!(instr, Instruction::JumpIfFalse(_, _));
+                    match (cond, target) {
+                        // Never taken
+                        (Value::Immediate(c), _) if (c == 0) != if_zero => {
+                            skipped.push(addr);
+                            addr = next;
+                            continue;
+                        }
+                        (Value::Immediate(_), Value::Immediate(t)) if t < 0 => {
+                            break Terminator::Interpret
+                        }
+                        (Value::Immediate(_), Value::Immediate(t)) => {
+                            break Block::jump(&ops, t as usize, next)
+                        }
+                        (Value::Immediate(_), Value::Relative(_)) => {
+                            break Terminator::Return { target }
+                        }
+                        (Value::Immediate(_), _) => break Terminator::Computed { target },
+                        _ => {
+                            break Terminator::Branch {
+                                cond,
+                                if_zero,
+                                target,
+                                next,
+                            }
+                        }
+                    }
+                }
+            };
+            ops.push((addr, op));
+            addr = next;
+        };
+        Block {
+            start,
+            ops,
+            terminator,
+            exit: addr,
+            skipped,
+        }
+    }
+
+    /// An unconditional jump is a call when the block stored the address following it
+    /// on the stack, as a return address
+    fn jump(ops: &[(usize, Op)], target: usize, next: usize) -> Terminator {
+        let stores_return = ops.iter().any(|(_, op)| match op {
+            Op::Copy {
+                dst: Value::Relative(_),
+                src: Value::Immediate(v),
+            } => *v == next as i64,
+            _ => false,
+        });
+        if stores_return {
+            Terminator::Call {
+                target,
+                returns_to: next,
+            }
+        } else {
+            Terminator::Jump(target)
+        }
+    }
+
+    /// Addresses of the cells holding the instructions of the block
+    fn cells(&self) -> impl Iterator<Item = usize> + '_ {
+        let ops = self
+            .ops
+            .iter()
+            .flat_map(|(addr, op)| *addr..*addr + op.len())
+            .chain(self.skipped.iter().flat_map(|addr| *addr..*addr + 3));
+        let exit_len = match self.terminator {
+            Terminator::Halt => 1,
+            Terminator::Interpret => 0,
+            _ => 3,
+        };
+        ops.chain(self.exit..self.exit + exit_len)
+    }
+
+    /// Entry addresses of the blocks that can follow this one
+    fn successors(&self) -> Vec<usize> {
+        match &self.terminator {
+            Terminator::Jump(target) => vec![*target],
+            Terminator::Call { target, returns_to } => vec![*target, *returns_to],
+            Terminator::Branch {
+                target: Value::Immediate(t),
+                next,
+                ..
+            } if *t >= 0 => vec![*t as usize, *next],
+            Terminator::Branch { next, .. } => vec![*next],
+            _ => vec![],
+        }
+    }
+}
+
+impl std::fmt::Display for Block {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        writeln!(f, "L{}:", self.start)?;
+        for (_, op) in self.ops.iter() {
+            writeln!(f, "    {}", op)?;
+        }
+        match &self.terminator {
+            Terminator::Jump(target) => writeln!(f, "    goto L{}", target),
+            Terminator::Branch {
+                cond,
+                if_zero,
+                target,
+                next,
+            } => {
+                let test = if *if_zero { "==" } else { "!=" };
+                writeln!(
+                    f,
+                    "    if {} {} 0 goto {} else L{}",
+                    cond, test, target, next
+                )
+            }
+            Terminator::Call { target, returns_to } => {
+                writeln!(f, "    call L{} then L{}", target, returns_to)
+            }
+            Terminator::Return { target } => writeln!(f, "    return {}", target),
+            Terminator::Computed { target } => writeln!(f, "    goto {}", target),
+            Terminator::Halt => writeln!(f, "    halt"),
+            Terminator::Interpret => writeln!(f, "    interpret {}", self.exit),
+        }
+    }
+}
+
+/// Intcode program lifted into basic blocks
+#[derive(Debug, Clone, Default)]
+pub struct IrProgram {
+    /// Blocks, by entry address. Blocks entered in the middle of another one overlap it
+    blocks: BTreeMap<usize, Block>,
+    /// Whether each cell holds a lifted instruction
+    code: Vec<bool>,
+}
+
+impl IrProgram {
+    /// Lifts the blocks reachable from address 0
+    pub fn compile(code: &[i64]) -> Self {
+        let memory = Memory::new(code.to_vec());
+        let mut program = IrProgram::default();
+        let mut todo = vec![0];
+        while let Some(addr) = todo.pop() {
+            if addr < code.len() && !program.blocks.contains_key(&addr) {
+                todo.extend(program.lift(&memory, addr).successors());
+            }
+        }
+        program
+    }
+
+    /// Lifts the block at the given address, marking its cells as code
+    fn lift(&mut self, memory: &Memory, addr: usize) -> &Block {
+        let block = Block::lift(memory, addr);
+        for cell in block.cells() {
+            if cell >= self.code.len() {
+                self.code.resize(cell + 1, false);
+            }
+            self.code[cell] = true;
+        }
+        self.blocks.entry(addr).or_insert(block)
+    }
+
+    pub fn blocks(&self) -> impl Iterator<Item = &Block> {
+        self.blocks.values()
+    }
+
+    /// Entry addresses of the functions called by the program
+    pub fn functions(&self) -> BTreeSet<usize> {
+        self.blocks
+            .values()
+            .filter_map(|block| match block.terminator {
+                Terminator::Call { target, .. } => Some(target),
+                _ => None,
+            })
+            .collect()
+    }
+
+    fn is_code(&self, addr: usize) -> bool {
+        self.code.get(addr).cloned().unwrap_or(false)
+    }
+}
+
+impl std::fmt::Display for IrProgram {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        for block in self.blocks.values() {
+            write!(f, "{}", block)?;
+        }
+        Ok(())
+    }
+}
+
+/// Registers and memory of an `IrComputer`
+#[derive(Debug, Clone, Default)]
+struct State {
+    memory: Memory,
+    pointer: usize,
+    relative_base: i64,
+    input: VecDeque<i64>,
+    output: VecDeque<i64>,
+    halt_on_output: bool,
+    halted: bool,
+}
+
+/// Why the execution of a block stopped
+enum Exit {
+    /// Continues with the block at the code pointer
+    Next,
+    Stop(RunState),
+    /// The program wrote over its code, or reached an instruction that could not be lifted
+    Interpret,
+}
+
+impl State {
+    fn address(&self, value: Value, instr: usize) -> Result<usize, ComputerError> {
+        let target = match value {
+            Value::Position(addr) => return Ok(addr),
+            Value::Relative(offset) => offset
+                .checked_add(self.relative_base)
+                .ok_or(ComputerError::Overflow { address: instr })?,
+            Value::Immediate(_) => return Err(ComputerError::WriteToImmediate { address: instr }),
+        };
+        if target < 0 {
+            return Err(ComputerError::NegativeAddress {
+                address: instr,
+                target,
+            });
+        }
+        Ok(target as usize)
+    }
+
+    fn load(&self, value: Value, instr: usize) -> Result<i64, ComputerError> {
+        match value {
+            Value::Immediate(x) => Ok(x),
+            _ => self.address(value, instr).map(|a| self.memory.get(a)),
+        }
+    }
+
+    /// Writes a value, returning the written address
+    fn store(&mut self, value: Value, val: i64, instr: usize) -> Result<usize, ComputerError> {
+        let addr = self.address(value, instr)?;
+        self.memory.set(addr, val);
+        Ok(addr)
+    }
+
+    fn jump(&mut self, target: i64, instr: usize) -> Result<(), ComputerError> {
+        if target < 0 {
+            return Err(ComputerError::NegativeAddress {
+                address: instr,
+                target,
+            });
+        }
+        self.pointer = target as usize;
+        Ok(())
+    }
+
+    fn run(&mut self, block: &Block, program: &IrProgram) -> Result<Exit, ComputerError> {
+        for (addr, op) in block.ops.iter() {
+            let addr = *addr;
+            let written = match op {
+                Op::Copy { dst, src } => {
+                    let val = self.load(*src, addr)?;
+                    Some(self.store(*dst, val, addr)?)
+                }
+                Op::Binary { op, dst, a, b } => {
+                    let (a, b) = (self.load(*a, addr)?, self.load(*b, addr)?);
+                    let val = op
+                        .apply(a, b)
+                        .ok_or(ComputerError::Overflow { address: addr })?;
+                    Some(self.store(*dst, val, addr)?)
+                }
+                Op::Input { dst } => {
+                    self.address(*dst, addr)?;
+                    match self.input.pop_front() {
+                        Some(val) => Some(self.store(*dst, val, addr)?),
+                        None => {
+                            self.pointer = addr;
+                            return Ok(Exit::Stop(RunState::NeedsInput));
+                        }
+                    }
+                }
+                Op::Output { src } => {
+                    let val = self.load(*src, addr)?;
+                    if self.halt_on_output {
+                        self.pointer = addr + op.len();
+                        return Ok(Exit::Stop(RunState::ProducedOutput(val)));
+                    }
+                    self.output.push_back(val);
+                    None
+                }
+                Op::AdjustBase { delta } => {
+                    self.relative_base = self
+                        .relative_base
+                        .checked_add(self.load(*delta, addr)?)
+                        .ok_or(ComputerError::Overflow { address: addr })?;
+                    None
+                }
+            };
+            if let Some(written) = written {
+                if program.is_code(written) {
+                    self.pointer = addr + op.len();
+                    return Ok(Exit::Interpret);
+                }
+            }
+        }
+
+        let exit = block.exit;
+        match &block.terminator {
+            Terminator::Jump(target) | Terminator::Call { target, .. } => self.pointer = *target,
+            Terminator::Branch {
+                cond,
+                if_zero,
+                target,
+                next,
+            } => {
+                if (self.load(*cond, exit)? == 0) == *if_zero {
+                    let target = self.load(*target, exit)?;
+                    self.jump(target, exit)?;
+                } else {
+                    self.pointer = *next;
+                }
+            }
+            Terminator::Return { target } | Terminator::Computed { target } => {
+                let target = self.load(*target, exit)?;
+                self.jump(target, exit)?;
+            }
+            Terminator::Halt => {
+                self.pointer = exit;
+                self.halted = true;
+                return Ok(Exit::Stop(RunState::Halted));
+            }
+            Terminator::Interpret => {
+                self.pointer = exit;
+                return Ok(Exit::Interpret);
+            }
+        }
+        Ok(Exit::Next)
+    }
+}
+
+/// Intcode computer executing the program lifted into basic blocks.
+/// Blocks entered at unforeseen addresses are lifted on the fly, and the plain
+/// interpreter takes over for good once the program writes over its own code
+#[derive(Debug, Clone)]
+pub struct IrComputer {
+    program: IrProgram,
+    state: State,
+    /// Plain interpreter, once it took over
+    fallback: Option<Computer>,
+}
+
+impl IrComputer {
+    pub fn new(code: Vec<i64>) -> Self {
+        IrComputer {
+            program: IrProgram::compile(&code),
+            state: State {
+                memory: Memory::new(code),
+                ..State::default()
+            },
+            fallback: None,
+        }
+    }
+
+    /// Stops the execution on each output, which is returned instead of queued
+    pub fn halt_on_output(mut self) -> Self {
+        self.state.halt_on_output = true;
+        self
+    }
+
+    pub fn program(&self) -> &IrProgram {
+        &self.program
+    }
+
+    /// Whether the plain interpreter took over
+    pub fn is_interpreting(&self) -> bool {
+        self.fallback.is_some()
+    }
+
+    pub fn input(&mut self, value: i64) {
+        match self.fallback.as_mut() {
+            Some(computer) => computer.input(value),
+            None => self.state.input.push_back(value),
+        }
+    }
+
+    pub fn get_next_output(&mut self) -> Option<i64> {
+        match self.fallback.as_mut() {
+            Some(computer) => computer.get_next_output(),
+            None => self.state.output.pop_front(),
+        }
+    }
+
+    pub fn get(&self, addr: usize) -> i64 {
+        match self.fallback.as_ref() {
+            Some(computer) => computer.get(addr),
+            None => self.state.memory.get(addr),
+        }
+    }
+
+    pub fn halted(&self) -> bool {
+        match self.fallback.as_ref() {
+            Some(computer) => computer.halted(),
+            None => self.state.halted,
+        }
+    }
+
+    /// Hands the state over to the plain interpreter
+    fn interpret(&mut self) -> &mut Computer {
+        let state = std::mem::take(&mut self.state);
+        let mut computer = Computer::new(vec![]);
+        computer.code = state.memory;
+        computer.pointer = state.pointer;
+        computer.relative_base = state.relative_base;
+        computer.input = state.input;
+        computer.output = state.output;
+        computer.halt_on_output = state.halt_on_output;
+        computer.halted = state.halted;
+        self.fallback.get_or_insert(computer)
+    }
+
+    /// Executes the program until it halts, needs input or produces an output
+    pub fn execute(&mut self) -> Result<RunState, ComputerError> {
+        if let Some(computer) = self.fallback.as_mut() {
+            return computer.execute();
+        }
+        loop {
+            let pointer = self.state.pointer;
+            if !self.program.blocks.contains_key(&pointer) {
+                self.program.lift(&self.state.memory, pointer);
+            }
+            let block = &self.program.blocks[&pointer];
+            match self.state.run(block, &self.program)? {
+                Exit::Next => (),
+                Exit::Stop(state) => return Ok(state),
+                Exit::Interpret => return self.interpret().execute(),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::{IrComputer, IrProgram, Op, Terminator};
+    use crate::intcode_computer::assembler::assemble;
+    use crate::intcode_computer::fixtures::{differential, SELF_PATCHING};
+    use crate::intcode_computer::{parse_input, Computer, ComputerError, RunState, Value};
+
+    #[test]
+    fn ir_lifting() {
+        let code = assemble(
+            "
+                    ARB #100
+                    ADD #ret, #0, [rb+0]
+                    MUL #2, #3, [rb+1]
+                    JT #1, #double
+            ret:    OUT [rb+1]
+                    HLT
+            double: MUL [rb+1], #2, [rb+1]
+                    JF #0, [rb+0]
+            ",
+        )
+        .unwrap();
+        let program = IrProgram::compile(&code);
+        let blocks: Vec<_> = program.blocks().collect();
+        assert_eq!(blocks.len(), 3);
+        assert_eq!(
+            blocks[0].ops[2].1,
+            Op::Copy {
+                dst: Value::Relative(1),
+                src: Value::Immediate(6)
+            }
+        );
+        assert_eq!(
+            blocks[0].terminator,
+            Terminator::Call {
+                target: 16,
+                returns_to: 13
+            }
+        );
+        assert_eq!(
+            blocks[2].terminator,
+            Terminator::Return {
+                target: Value::Relative(0)
+            }
+        );
+        assert_eq!(
+            program.functions().into_iter().collect::<Vec<_>>(),
+            vec![16]
+        );
+        assert!(program.to_string().contains("    call L16 then L13\n"));
+
+        let mut computer = IrComputer::new(code);
+        assert_eq!(computer.execute(), Ok(RunState::Halted));
+        assert_eq!(computer.get_next_output(), Some(12));
+        assert!(!computer.is_interpreting());
+    }
+
+    #[test]
+    fn ir_matches_computer() {
+        for code in differential() {
+            let mut reference = Computer::new(code.clone());
+            let mut computer = IrComputer::new(code);
+            assert_eq!(computer.execute(), reference.execute());
+            let output: Vec<i64> = std::iter::from_fn(|| computer.get_next_output()).collect();
+            assert_eq!(reference.output, output);
+        }
+
+        // Reads two numbers and outputs whether the first is below the second
+        let code = parse_input("3,9,3,10,7,9,10,11,104,0,0,0");
+        let mut reference = Computer::new(code.clone());
+        let mut computer = IrComputer::new(code);
+        assert_eq!(computer.execute(), Ok(RunState::NeedsInput));
+        computer.input(3);
+        computer.input(5);
+        reference.input(3);
+        reference.input(5);
+        assert_eq!(computer.execute(), reference.execute());
+
+        // Patches its own output instruction : the plain interpreter takes over
+        let mut computer = IrComputer::new(assemble(SELF_PATCHING).unwrap());
+        assert_eq!(computer.execute(), Ok(RunState::Halted));
+        assert!(computer.is_interpreting());
+
+        // Clears the condition of a jump never taken so far, then goes back to it
+        let code = parse_input("1106,1,11,1101,0,0,1,1105,1,0,99,104,7,99");
+        let mut computer = IrComputer::new(code);
+        assert_eq!(computer.execute(), Ok(RunState::Halted));
+        assert_eq!(computer.get_next_output(), Some(7));
+
+        // A relative operand multiplied by 0 still overflows
+        let code = parse_input("109,1,1202,9223372036854775807,0,10,99");
+        assert_eq!(
+            IrComputer::new(code).execute(),
+            Err(ComputerError::Overflow { address: 2 })
+        );
+    }
+}
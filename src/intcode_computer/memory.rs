@@ -0,0 +1,139 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+
+/// Addresses from this one onwards are stored in the sparse part of the memory
+const DENSE_LIMIT: usize = 1 << 20;
+
+/// Memory of the Intcode computer.
+/// Every cell starts at 0, and the memory grows on demand when written to.
+/// Low addresses are stored contiguously, very high addresses are stored sparsely.
+/// Cloning is cheap : the cells are only copied when one of the clones is written to.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Memory {
+    /// Cells from address 0 up to the highest written address (below `DENSE_LIMIT`)
+    dense: Arc<Vec<i64>>,
+    /// Cells written at or above `DENSE_LIMIT`
+    sparse: Arc<HashMap<usize, i64>>,
+}
+
+impl Memory {
+    /// Creates a memory initialized with the given program
+    pub fn new(code: Vec<i64>) -> Self {
+        Memory {
+            dense: Arc::new(code),
+            sparse: Arc::new(HashMap::new()),
+        }
+    }
+
+    /// Creates a memory from its contiguous and sparse cells
+    pub fn from_parts(dense: Vec<i64>, sparse: impl IntoIterator<Item = (usize, i64)>) -> Self {
+        let mut memory = Memory::new(dense);
+        for (addr, value) in sparse {
+            memory.set(addr, value);
+        }
+        memory
+    }
+
+    /// Tells whether both memories still share their cells, none of them being written
+    /// to since one was cloned from the other
+    pub fn shares_cells(&self, other: &Memory) -> bool {
+        Arc::ptr_eq(&self.dense, &other.dense) && Arc::ptr_eq(&self.sparse, &other.sparse)
+    }
+
+    /// Contiguous cells, from address 0
+    pub fn dense_cells(&self) -> &[i64] {
+        &self.dense
+    }
+
+    /// Cells stored sparsely, at very high addresses
+    pub fn sparse_cells(&self) -> impl Iterator<Item = (usize, i64)> + '_ {
+        self.sparse.iter().map(|(addr, value)| (*addr, *value))
+    }
+
+    /// Gets the value at the given address. Unwritten cells are 0
+    pub fn get(&self, addr: usize) -> i64 {
+        match self.dense.get(addr) {
+            Some(val) => *val,
+            None => self.sparse.get(&addr).cloned().unwrap_or(0),
+        }
+    }
+
+    /// Sets the value at the given address, growing the memory if needed
+    pub fn set(&mut self, addr: usize, value: i64) {
+        if addr < self.dense.len() {
+            Arc::make_mut(&mut self.dense)[addr] = value;
+        } else if addr < DENSE_LIMIT {
+            let dense = Arc::make_mut(&mut self.dense);
+            dense.resize(addr + 1, 0);
+            dense[addr] = value;
+        } else {
+            Arc::make_mut(&mut self.sparse).insert(addr, value);
+        }
+    }
+
+    /// Returns one past the highest address that was loaded or written
+    pub fn len(&self) -> usize {
+        self.sparse
+            .keys()
+            .max()
+            .map(|addr| addr + 1)
+            .unwrap_or_else(|| self.dense.len())
+    }
+
+    /// Tells whether nothing was ever loaded or written in the memory
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Tells whether the given address was loaded or written
+    pub fn contains(&self, addr: usize) -> bool {
+        addr < self.dense.len() || self.sparse.contains_key(&addr)
+    }
+}
+
+impl From<Vec<i64>> for Memory {
+    fn from(code: Vec<i64>) -> Self {
+        Memory::new(code)
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::{Memory, DENSE_LIMIT};
+
+    #[test]
+    fn memory_grows_on_demand() {
+        let mut memory = Memory::new(vec![1, 2, 3]);
+        assert_eq!(memory.get(1), 2);
+        assert_eq!(memory.get(500), 0);
+        assert_eq!(memory.len(), 3);
+
+        memory.set(500, 42);
+        assert_eq!(memory.get(500), 42);
+        assert_eq!(memory.get(499), 0);
+        assert_eq!(memory.len(), 501);
+
+        memory.set(DENSE_LIMIT * 1000, 7);
+        assert_eq!(memory.get(DENSE_LIMIT * 1000), 7);
+        assert!(memory.contains(DENSE_LIMIT * 1000));
+        assert!(!memory.contains(DENSE_LIMIT * 1000 - 1));
+        assert_eq!(memory.len(), DENSE_LIMIT * 1000 + 1);
+    }
+
+    #[test]
+    fn memory_copy_on_write() {
+        let mut memory = Memory::new(vec![1, 2, 3]);
+        let copy = memory.clone();
+        memory.set(0, 10);
+        memory.set(DENSE_LIMIT, 5);
+        assert_eq!(copy.get(0), 1);
+        assert_eq!(copy.get(DENSE_LIMIT), 0);
+        assert_eq!(memory.get(0), 10);
+
+        let rebuilt = Memory::from_parts(
+            memory.dense_cells().to_vec(),
+            memory.sparse_cells().collect::<Vec<_>>(),
+        );
+        assert_eq!(rebuilt, memory);
+    }
+}
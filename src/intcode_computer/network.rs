@@ -0,0 +1,344 @@
+use super::{Computer, ComputerError, RunState};
+use std::collections::BTreeMap;
+
+/// Default number of instructions a machine may execute in a round, before it needs input
+const MACHINE_CYCLES: u64 = 1_000_000;
+
+/// Packet sent over the network, its destination being given separately
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Packet {
+    pub x: i64,
+    pub y: i64,
+}
+
+/// What a packet handler asks the network to do
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Action {
+    Continue,
+    /// Sends a packet to the given address
+    Send(i64, Packet),
+    /// Stops the network with the given value
+    Stop(i64),
+}
+
+/// Handles the packets sent to a special address, with no machine behind it
+pub trait PacketHandler {
+    /// Receives a packet sent to the address of the handler
+    fn receive(&mut self, packet: Packet) -> Action;
+
+    /// Called once the network is idle
+    fn idle(&mut self) -> Action {
+        Action::Continue
+    }
+}
+
+impl<F: FnMut(Packet) -> Action> PacketHandler for F {
+    fn receive(&mut self, packet: Packet) -> Action {
+        self(packet)
+    }
+}
+
+/// Keeps the last packet it received, and sends it to the address 0 when the network is idle.
+/// Stops the network when it would send the same Y value twice in a row
+#[derive(Debug, Default)]
+pub struct Nat {
+    last: Option<Packet>,
+    last_sent: Option<i64>,
+}
+
+impl PacketHandler for Nat {
+    fn receive(&mut self, packet: Packet) -> Action {
+        self.last = Some(packet);
+        Action::Continue
+    }
+
+    fn idle(&mut self) -> Action {
+        match self.last {
+            Some(packet) if self.last_sent == Some(packet.y) => Action::Stop(packet.y),
+            Some(packet) => {
+                self.last_sent = Some(packet.y);
+                Action::Send(0, packet)
+            }
+            None => Action::Continue,
+        }
+    }
+}
+
+/// Errors that can happen while running a network
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum NetworkError {
+    /// A machine failed
+    Computer(ComputerError),
+    /// A packet was sent to an address with neither a machine nor a handler
+    UnknownAddress { destination: i64 },
+    /// The machine at the address ran out of cycles without needing input
+    BudgetExhausted { address: usize },
+}
+
+impl std::fmt::Display for NetworkError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            NetworkError::Computer(e) => write!(f, "{}", e),
+            NetworkError::UnknownAddress { destination } => {
+                write!(f, "packet sent to unknown address {}", destination)
+            }
+            NetworkError::BudgetExhausted { address } => {
+                write!(f, "machine {} ran out of cycles", address)
+            }
+        }
+    }
+}
+
+impl std::error::Error for NetworkError {}
+
+impl From<ComputerError> for NetworkError {
+    fn from(e: ComputerError) -> Self {
+        NetworkError::Computer(e)
+    }
+}
+
+/// Reason why the network stopped
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NetworkStop {
+    /// The handler at the given address stopped the network
+    Stopped { address: i64, value: i64 },
+    /// The network is idle, and no handler woke it up
+    Idle,
+    /// Every machine halted
+    Halted,
+}
+
+/// Machines running the same program, exchanging packets.
+/// Each machine receives its address as first input, then sends packets as
+/// `destination, x, y` outputs, and reads `-1` when no packet is waiting.
+///
+/// The machines run in turns, in address order, each one until it needs input,
+/// which makes the simulation deterministic. A machine executing more instructions than
+/// its cycle budget in a round stops the network
+pub struct Network {
+    machines: Vec<Computer>,
+    handlers: BTreeMap<i64, Box<dyn PacketHandler>>,
+    rounds: usize,
+}
+
+impl Network {
+    /// Creates a network of `size` machines running the given program
+    pub fn new(code: Vec<i64>, size: usize) -> Self {
+        let machine = Computer::new(code).with_cycle_budget(MACHINE_CYCLES);
+        let machines = (0..size)
+            .map(|address| {
+                let mut machine = machine.clone();
+                machine.input(address as i64);
+                machine
+            })
+            .collect();
+        Network {
+            machines,
+            handlers: BTreeMap::new(),
+            rounds: 0,
+        }
+    }
+
+    /// Sets the number of instructions each machine may execute in a round
+    pub fn with_cycle_budget(mut self, cycles: u64) -> Self {
+        self.machines = std::mem::take(&mut self.machines)
+            .into_iter()
+            .map(|machine| machine.with_cycle_budget(cycles))
+            .collect();
+        self
+    }
+
+    /// Sets the handler of the packets sent to the given address
+    pub fn add_handler(&mut self, address: i64, handler: impl PacketHandler + 'static) {
+        self.handlers.insert(address, Box::new(handler));
+    }
+
+    pub fn machines(&self) -> &[Computer] {
+        &self.machines
+    }
+
+    /// Number of rounds executed so far
+    pub fn rounds(&self) -> usize {
+        self.rounds
+    }
+
+    /// Delivers a packet to a machine or a handler
+    fn route(
+        &mut self,
+        destination: i64,
+        packet: Packet,
+    ) -> Result<Option<NetworkStop>, NetworkError> {
+        if destination >= 0 && (destination as usize) < self.machines.len() {
+            let machine = &mut self.machines[destination as usize];
+            machine.input(packet.x);
+            machine.input(packet.y);
+            return Ok(None);
+        }
+        let action = match self.handlers.get_mut(&destination) {
+            Some(handler) => handler.receive(packet),
+            None => return Err(NetworkError::UnknownAddress { destination }),
+        };
+        self.apply(destination, action)
+    }
+
+    fn apply(&mut self, address: i64, action: Action) -> Result<Option<NetworkStop>, NetworkError> {
+        match action {
+            Action::Continue => Ok(None),
+            Action::Send(destination, packet) => self.route(destination, packet),
+            Action::Stop(value) => Ok(Some(NetworkStop::Stopped { address, value })),
+        }
+    }
+
+    /// Runs every machine once, until it needs input, then delivers the packets they sent
+    pub fn round(&mut self) -> Result<Option<NetworkStop>, NetworkError> {
+        self.rounds += 1;
+        let mut idle = true;
+        let mut packets = vec![];
+        for (address, machine) in self.machines.iter_mut().enumerate() {
+            if machine.halted() {
+                continue;
+            }
+            if machine.pending_input().next().is_none() {
+                machine.input(-1);
+            } else {
+                idle = false;
+            }
+            if machine.execute()? == RunState::BudgetExhausted {
+                return Err(NetworkError::BudgetExhausted { address });
+            }
+            while machine.output.len() >= 3 {
+                let mut next = || machine.output.pop_front().unwrap();
+                let (destination, x, y) = (next(), next(), next());
+                packets.push((destination, Packet { x, y }));
+            }
+        }
+        if self.machines.iter().all(|m| m.halted()) {
+            return Ok(Some(NetworkStop::Halted));
+        }
+
+        if packets.is_empty() && idle {
+            let addresses: Vec<i64> = self.handlers.keys().cloned().collect();
+            let mut woken = false;
+            for address in addresses {
+                let action = self.handlers.get_mut(&address).unwrap().idle();
+                woken |= action != Action::Continue;
+                if let Some(stop) = self.apply(address, action)? {
+                    return Ok(Some(stop));
+                }
+            }
+            if !woken {
+                return Ok(Some(NetworkStop::Idle));
+            }
+        }
+        for (destination, packet) in packets {
+            if let Some(stop) = self.route(destination, packet)? {
+                return Ok(Some(stop));
+            }
+        }
+        Ok(None)
+    }
+
+    /// Runs rounds until the network stops
+    pub fn run(&mut self) -> Result<NetworkStop, NetworkError> {
+        loop {
+            if let Some(stop) = self.round()? {
+                return Ok(stop);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::{Action, Nat, Network, NetworkError, NetworkStop, Packet};
+    use crate::intcode_computer::assembler::assemble;
+
+    /// The machine 0 starts by sending the packet (1, 1) to the machine 2.
+    /// A machine receiving (x, y) sends (its address, y + its address) to the machine x,
+    /// or to 255 once y reaches 10
+    const NIC: &str = "
+                INP [addr]
+                JT [addr], #loop
+                OUT #2
+                OUT #1
+                OUT #1
+        loop:   INP [x]
+                EQ [x], #-1, [tmp]
+                JT [tmp], #loop
+                INP [y]
+                ADD [y], [addr], [sum]
+                LT [y], #10, [tmp]
+                JT [tmp], #forward
+                OUT #255
+                JT #1, #send
+        forward: OUT [x]
+        send:   OUT [addr]
+                OUT [sum]
+                JT #1, #loop
+        addr:   DB 0
+        x:      DB 0
+        y:      DB 0
+        sum:    DB 0
+        tmp:    DB 0
+    ";
+
+    #[test]
+    fn network_routing_and_nat() {
+        let code = assemble(NIC).unwrap();
+
+        let mut network = Network::new(code.clone(), 3);
+        network.add_handler(255, |p: Packet| Action::Stop(p.x * 100 + p.y));
+        assert_eq!(
+            network.run(),
+            Ok(NetworkStop::Stopped {
+                address: 255,
+                value: 212
+            })
+        );
+
+        // The NAT wakes up the machine 0, which sends back the same Y value
+        let mut network = Network::new(code.clone(), 3);
+        network.add_handler(255, Nat::default());
+        assert_eq!(
+            network.run(),
+            Ok(NetworkStop::Stopped {
+                address: 255,
+                value: 12
+            })
+        );
+        let rounds = network.rounds();
+        let mut replayed = Network::new(code.clone(), 3);
+        replayed.add_handler(255, Nat::default());
+        replayed.run().unwrap();
+        assert_eq!(replayed.rounds(), rounds);
+
+        let mut network = Network::new(code.clone(), 3);
+        assert_eq!(
+            network.run(),
+            Err(NetworkError::UnknownAddress { destination: 255 })
+        );
+        let mut network = Network::new(code, 1);
+        assert_eq!(
+            network.run(),
+            Err(NetworkError::UnknownAddress { destination: 2 })
+        );
+
+        // The machine 1 spins without ever reading input again
+        let spin = assemble(
+            "
+                    INP [addr]
+                    JF [addr], #wait
+            spin:   JT #1, #spin
+            wait:   INP [addr]
+                    JT #1, #wait
+            addr:   DB 0
+            ",
+        )
+        .unwrap();
+        let mut network = Network::new(spin, 2).with_cycle_budget(1000);
+        assert_eq!(
+            network.run(),
+            Err(NetworkError::BudgetExhausted { address: 1 })
+        );
+    }
+}
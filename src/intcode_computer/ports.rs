@@ -0,0 +1,205 @@
+use std::collections::VecDeque;
+use std::sync::mpsc::{Receiver, Sender};
+use std::sync::{Arc, Mutex};
+
+/// Source of the values read by `Inp` instructions
+pub trait InputPort: Send {
+    /// Returns the next value, or None if no value is available
+    fn read(&mut self) -> Option<i64>;
+}
+
+/// Destination of the values produced by `Out` instructions
+pub trait OutputPort: Send {
+    fn write(&mut self, value: i64);
+}
+
+impl InputPort for VecDeque<i64> {
+    fn read(&mut self) -> Option<i64> {
+        self.pop_front()
+    }
+}
+
+impl OutputPort for VecDeque<i64> {
+    fn write(&mut self, value: i64) {
+        self.push_back(value);
+    }
+}
+
+/// Input computed on demand by a closure
+pub struct FnInput<F>(pub F);
+
+impl<F: FnMut() -> Option<i64> + Send> InputPort for FnInput<F> {
+    fn read(&mut self) -> Option<i64> {
+        (self.0)()
+    }
+}
+
+/// Output handed to a closure
+pub struct FnOutput<F>(pub F);
+
+impl<F: FnMut(i64) + Send> OutputPort for FnOutput<F> {
+    fn write(&mut self, value: i64) {
+        (self.0)(value)
+    }
+}
+
+/// Input taken from an iterator
+pub struct IterInput<I>(pub I);
+
+impl<I: Iterator<Item = i64> + Send> InputPort for IterInput<I> {
+    fn read(&mut self) -> Option<i64> {
+        self.0.next()
+    }
+}
+
+/// Blocks until a value is received. Returns None once every sender is gone
+impl InputPort for Receiver<i64> {
+    fn read(&mut self) -> Option<i64> {
+        self.recv().ok()
+    }
+}
+
+/// Values sent once the receiver is gone are dropped
+impl OutputPort for Sender<i64> {
+    fn write(&mut self, value: i64) {
+        let _ = self.send(value);
+    }
+}
+
+/// Queue shared by its clones, to plug the output of a computer into the input of another
+#[derive(Debug, Clone, Default)]
+pub struct SharedQueue(Arc<Mutex<VecDeque<i64>>>);
+
+impl SharedQueue {
+    pub fn new() -> Self {
+        SharedQueue::default()
+    }
+
+    pub fn push(&self, value: i64) {
+        self.0.lock().unwrap().push_back(value);
+    }
+
+    pub fn pop(&self) -> Option<i64> {
+        self.0.lock().unwrap().pop_front()
+    }
+
+    pub fn len(&self) -> usize {
+        self.0.lock().unwrap().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Copies the values currently in the queue
+    pub fn values(&self) -> Vec<i64> {
+        self.0.lock().unwrap().iter().cloned().collect()
+    }
+}
+
+impl InputPort for SharedQueue {
+    fn read(&mut self) -> Option<i64> {
+        self.pop()
+    }
+}
+
+impl OutputPort for SharedQueue {
+    fn write(&mut self, value: i64) {
+        self.push(value);
+    }
+}
+
+/// Ports plugged into a computer. They are not carried over when the computer is cloned
+#[derive(Default)]
+pub(super) struct Ports {
+    /// Read when the input queue of the computer is empty
+    pub input: Option<Box<dyn InputPort>>,
+    /// Receives the outputs instead of the output queue of the computer
+    pub output: Option<Box<dyn OutputPort>>,
+}
+
+impl Clone for Ports {
+    fn clone(&self) -> Self {
+        Ports::default()
+    }
+}
+
+impl std::fmt::Debug for Ports {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.debug_struct("Ports")
+            .field("input", &self.input.is_some())
+            .field("output", &self.output.is_some())
+            .finish()
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::{FnInput, FnOutput, IterInput, SharedQueue};
+    use crate::intcode_computer::assembler::assemble;
+    use crate::intcode_computer::{Computer, RunState};
+    use std::sync::mpsc::channel;
+    use std::sync::{Arc, Mutex};
+
+    /// Outputs the double of each input, until it reads 0
+    const DOUBLER: &str = "
+        loop:   INP [value]
+                JF [value], #end
+                MUL [value], #2, [value]
+                OUT [value]
+                JT #1, #loop
+        end:    HLT
+        value:  DB 0
+    ";
+
+    #[test]
+    fn ports_closures_and_iterators() {
+        let code = assemble(DOUBLER).unwrap();
+
+        // The input is computed from the last output, as a camera would read a map
+        let last = Arc::new(Mutex::new(1));
+        let camera = last.clone();
+        let recorder = last.clone();
+        let mut computer = Computer::new(code.clone())
+            .with_input_port(FnInput(move || {
+                let value = *camera.lock().unwrap();
+                Some(if value < 10 { value } else { 0 })
+            }))
+            .with_output_port(FnOutput(move |v| *recorder.lock().unwrap() = v));
+        assert_eq!(computer.execute(), Ok(RunState::Halted));
+        assert_eq!(*last.lock().unwrap(), 16);
+        assert!(computer.output.is_empty());
+
+        let mut computer = Computer::new(code).with_input_port(IterInput(vec![3, 4].into_iter()));
+        assert_eq!(computer.execute(), Ok(RunState::NeedsInput));
+        assert_eq!(computer.output, vec![6, 8]);
+        computer.input(0);
+        assert_eq!(computer.execute(), Ok(RunState::Halted));
+    }
+
+    #[test]
+    fn ports_connected_computers() {
+        let code = assemble(DOUBLER).unwrap();
+        let link = SharedQueue::new();
+        let result = SharedQueue::new();
+        let mut first = Computer::new(code.clone()).with_output_port(link.clone());
+        let mut second = Computer::new(code.clone())
+            .with_input_port(link.clone())
+            .with_output_port(result.clone());
+
+        first.input(1);
+        first.input(5);
+        assert_eq!(first.execute(), Ok(RunState::NeedsInput));
+        assert_eq!(link.values(), vec![2, 10]);
+        assert_eq!(second.execute(), Ok(RunState::NeedsInput));
+        assert_eq!(result.values(), vec![4, 20]);
+        assert!(link.is_empty());
+
+        let (tx, rx) = channel();
+        let mut third = Computer::new(code).with_input_port(rx);
+        tx.send(7).unwrap();
+        tx.send(0).unwrap();
+        assert_eq!(third.execute(), Ok(RunState::Halted));
+        assert_eq!(third.output, vec![14]);
+    }
+}
@@ -0,0 +1,177 @@
+use super::{Computer, Instruction, Value};
+use std::collections::BTreeMap;
+
+/// Number of entries shown in each ranking of a report
+const REPORT_TOP: usize = 10;
+
+/// Memory accesses of an instruction, resolved before its execution
+pub(super) struct Sample {
+    pointer: usize,
+    mnemonic: &'static str,
+    modes: Vec<&'static str>,
+    reads: Vec<usize>,
+    write: Option<usize>,
+}
+
+impl Sample {
+    pub(super) fn before(computer: &Computer, instr: &Instruction) -> Self {
+        let destination = instr.destination();
+        // The destination is always the last operand, and is not read
+        let mut read = instr.operands();
+        if destination.is_some() {
+            read.pop();
+        }
+        let reads = read
+            .into_iter()
+            .filter(|v| !matches!(v, Value::Immediate(_)))
+            .filter_map(|v| computer.address(v).ok())
+            .collect();
+        let modes = instr
+            .operands()
+            .into_iter()
+            .map(|v| match v {
+                Value::Position(_) => "position",
+                Value::Immediate(_) => "immediate",
+                Value::Relative(_) => "relative",
+            })
+            .collect();
+        Sample {
+            pointer: computer.pointer,
+            mnemonic: instr.mnemonic(),
+            modes,
+            reads,
+            write: destination.and_then(|d| computer.address(d).ok()),
+        }
+    }
+}
+
+/// Execution statistics of a computer
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Profile {
+    /// Number of executed instructions
+    pub cycles: u64,
+    /// Executed instructions, per mnemonic
+    pub opcodes: BTreeMap<&'static str, u64>,
+    /// Executed instructions, per address
+    pub addresses: BTreeMap<usize, u64>,
+    /// Operands of the executed instructions, per parameter mode
+    pub modes: BTreeMap<&'static str, u64>,
+    /// Operand reads, per memory cell
+    pub reads: BTreeMap<usize, u64>,
+    /// Writes, per memory cell
+    pub writes: BTreeMap<usize, u64>,
+}
+
+/// Entries of the map with the highest counts, the lowest keys first on ties
+fn top<K: Copy + Ord>(map: &BTreeMap<K, u64>, n: usize) -> Vec<(K, u64)> {
+    let mut entries: Vec<(K, u64)> = map.iter().map(|(k, v)| (*k, *v)).collect();
+    entries.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+    entries.truncate(n);
+    entries
+}
+
+fn add_counts<K: Copy + Ord>(into: &mut BTreeMap<K, u64>, from: &BTreeMap<K, u64>) {
+    for (k, v) in from.iter() {
+        *into.entry(*k).or_insert(0) += v;
+    }
+}
+
+impl Profile {
+    pub(super) fn record(&mut self, sample: Sample) {
+        self.cycles += 1;
+        *self.opcodes.entry(sample.mnemonic).or_insert(0) += 1;
+        *self.addresses.entry(sample.pointer).or_insert(0) += 1;
+        for mode in sample.modes {
+            *self.modes.entry(mode).or_insert(0) += 1;
+        }
+        for addr in sample.reads {
+            *self.reads.entry(addr).or_insert(0) += 1;
+        }
+        if let Some(addr) = sample.write {
+            *self.writes.entry(addr).or_insert(0) += 1;
+        }
+    }
+
+    /// Adds up the statistics of another profile, to aggregate several runs
+    pub fn merge(&mut self, other: &Profile) {
+        self.cycles += other.cycles;
+        add_counts(&mut self.opcodes, &other.opcodes);
+        add_counts(&mut self.addresses, &other.addresses);
+        add_counts(&mut self.modes, &other.modes);
+        add_counts(&mut self.reads, &other.reads);
+        add_counts(&mut self.writes, &other.writes);
+    }
+
+    /// Writes the report of the profile to a file
+    pub fn save(&self, path: impl AsRef<std::path::Path>) -> std::io::Result<()> {
+        std::fs::write(path, self.to_string())
+    }
+
+    /// Addresses of the most executed instructions, with their count
+    pub fn hot_spots(&self, n: usize) -> Vec<(usize, u64)> {
+        top(&self.addresses, n)
+    }
+}
+
+/// Formats the profile as a report, with the most executed instructions
+/// and the most accessed memory cells
+impl std::fmt::Display for Profile {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let percent = |count: u64, total: u64| 100.0 * count as f64 / total.max(1) as f64;
+        writeln!(f, "cycles: {}", self.cycles)?;
+        writeln!(f, "opcodes:")?;
+        for (mnemonic, count) in top(&self.opcodes, self.opcodes.len()) {
+            let share = percent(count, self.cycles);
+            writeln!(f, "  {:<10} {:>12} {:>6.1}%", mnemonic, count, share)?;
+        }
+        writeln!(f, "parameter modes:")?;
+        let operands = self.modes.values().sum();
+        for (mode, count) in top(&self.modes, self.modes.len()) {
+            let share = percent(count, operands);
+            writeln!(f, "  {:<10} {:>12} {:>6.1}%", mode, count, share)?;
+        }
+        let rankings = [
+            ("hot spots", &self.addresses),
+            ("memory reads", &self.reads),
+            ("memory writes", &self.writes),
+        ];
+        for (title, map) in rankings.iter() {
+            writeln!(f, "{}:", title)?;
+            for (addr, count) in top(map, REPORT_TOP) {
+                writeln!(f, "  {:<10} {:>12}", addr, count)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use crate::intcode_computer::assembler::assemble;
+    use crate::intcode_computer::fixtures::COUNTDOWN;
+    use crate::intcode_computer::{Computer, RunState};
+
+    #[test]
+    fn profiler_statistics() {
+        let code = assemble(COUNTDOWN).unwrap();
+        let mut computer = Computer::new(code.clone()).record_profile();
+        assert_eq!(computer.execute(), Ok(RunState::Halted));
+        let profile = computer.take_profile().unwrap();
+
+        assert_eq!(profile.cycles, 8);
+        let opcodes: Vec<_> = profile.opcodes.clone().into_iter().collect();
+        assert_eq!(opcodes, vec![("ADD", 3), ("HLT", 1), ("JT", 2), ("OUT", 2)]);
+        assert_eq!(profile.modes["immediate"], 6);
+        assert_eq!(profile.modes["position"], 9);
+        assert_eq!(profile.hot_spots(2), vec![(4, 2), (6, 2)]);
+        assert_eq!(profile.reads[&14], 6);
+        assert_eq!(profile.writes[&14], 3);
+        assert!(profile.to_string().starts_with("cycles: 8\n"));
+
+        // Profiles of several runs add up
+        let mut total = profile.clone();
+        total.merge(&profile);
+        assert_eq!(total.cycles, 16);
+        assert_eq!(total.writes[&14], 6);
+    }
+}
@@ -0,0 +1,196 @@
+use super::arithmetic::Arithmetic;
+use super::{Computer, Memory};
+use serde::{Deserialize, Serialize};
+
+/// Version of the snapshot format, bumped on incompatible changes
+const SNAPSHOT_VERSION: u32 = 1;
+
+/// Reasons why a snapshot cannot be restored
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SnapshotError {
+    /// The snapshot was written in another version of the format
+    UnsupportedVersion { version: u32 },
+    /// A big integer cell is not a valid decimal number
+    InvalidBigCell { address: usize, value: String },
+}
+
+impl std::fmt::Display for SnapshotError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            SnapshotError::UnsupportedVersion { version } => write!(
+                f,
+                "unsupported snapshot version {} (expected {})",
+                version, SNAPSHOT_VERSION
+            ),
+            SnapshotError::InvalidBigCell { address, value } => {
+                write!(f, "invalid big integer {} at address {}", value, address)
+            }
+        }
+    }
+}
+
+impl std::error::Error for SnapshotError {}
+
+/// Full state of a computer, in a stable serializable form
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Snapshot {
+    /// Version of the snapshot format
+    pub version: u32,
+    /// Contiguous memory cells, from address 0
+    pub memory: Vec<i64>,
+    /// Memory cells at very high addresses, sorted by address
+    pub sparse_memory: Vec<(usize, i64)>,
+    pub pointer: usize,
+    pub relative_base: i64,
+    /// Values waiting to be read by `Inp` instructions
+    pub input: Vec<i64>,
+    /// Values produced and not consumed yet
+    pub output: Vec<i64>,
+    pub halt_on_output: bool,
+    pub halted: bool,
+    #[serde(default)]
+    pub arithmetic: Arithmetic,
+    /// Cells holding values beyond the range of an i64, in decimal, sorted by address
+    #[serde(default)]
+    pub big_cells: Vec<(usize, String)>,
+}
+
+impl Snapshot {
+    /// Serializes the snapshot to JSON
+    pub fn to_json(&self) -> String {
+        serde_json::to_string(self).expect("Failed to serialize snapshot")
+    }
+
+    /// Deserializes a snapshot from JSON
+    pub fn from_json(json: &str) -> serde_json::Result<Self> {
+        serde_json::from_str(json)
+    }
+
+    /// Writes the snapshot to a file, as JSON
+    pub fn save(&self, path: impl AsRef<std::path::Path>) -> std::io::Result<()> {
+        std::fs::write(path, self.to_json())
+    }
+
+    /// Reads a snapshot from a JSON file. Snapshots of another version are rejected
+    pub fn load(path: impl AsRef<std::path::Path>) -> std::io::Result<Self> {
+        let json = std::fs::read_to_string(path)?;
+        let snapshot = Snapshot::from_json(&json)?;
+        snapshot
+            .check_version()
+            .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))?;
+        Ok(snapshot)
+    }
+
+    fn check_version(&self) -> Result<(), SnapshotError> {
+        if self.version != SNAPSHOT_VERSION {
+            return Err(SnapshotError::UnsupportedVersion {
+                version: self.version,
+            });
+        }
+        Ok(())
+    }
+}
+
+impl Computer {
+    /// Captures the full state of the computer.
+    /// The trace and the instruction set are not part of it
+    pub fn snapshot(&self) -> Snapshot {
+        let mut sparse_memory: Vec<(usize, i64)> = self.code.sparse_cells().collect();
+        sparse_memory.sort();
+        Snapshot {
+            version: SNAPSHOT_VERSION,
+            memory: self.code.dense_cells().to_vec(),
+            sparse_memory,
+            pointer: self.pointer,
+            relative_base: self.relative_base,
+            input: self.input.iter().cloned().collect(),
+            output: self.output.iter().cloned().collect(),
+            halt_on_output: self.halt_on_output,
+            halted: self.halted,
+            arithmetic: self.arithmetic,
+            big_cells: self
+                .big_cells
+                .iter()
+                .map(|(addr, value)| (*addr, value.to_string()))
+                .collect(),
+        }
+    }
+
+    /// Creates a computer in the state captured by the snapshot
+    pub fn restore(snapshot: Snapshot) -> Result<Self, SnapshotError> {
+        snapshot.check_version()?;
+        let mut computer = Computer::new(vec![]);
+        computer.code = Memory::from_parts(snapshot.memory, snapshot.sparse_memory);
+        computer.pointer = snapshot.pointer;
+        computer.relative_base = snapshot.relative_base;
+        computer.input = snapshot.input.into_iter().collect();
+        computer.output = snapshot.output.into_iter().collect();
+        computer.halt_on_output = snapshot.halt_on_output;
+        computer.halted = snapshot.halted;
+        computer.arithmetic = snapshot.arithmetic;
+        computer.big_cells = snapshot
+            .big_cells
+            .into_iter()
+            .map(|(address, value)| match value.parse() {
+                Ok(big) => Ok((address, big)),
+                Err(_) => Err(SnapshotError::InvalidBigCell { address, value }),
+            })
+            .collect::<Result<_, _>>()?;
+        Ok(computer)
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::{Snapshot, SnapshotError, SNAPSHOT_VERSION};
+    use crate::intcode_computer::fixtures::RUNNING_SUM;
+    use crate::intcode_computer::{parse_input, Computer, RunState};
+
+    #[test]
+    fn snapshot_restore() {
+        let code = parse_input(RUNNING_SUM);
+        let mut computer = Computer::new(code);
+        computer.input(5);
+        assert_eq!(computer.execute(), Ok(RunState::NeedsInput));
+        computer.set(5_000_000, 42);
+
+        let snapshot = computer.snapshot();
+        let json = snapshot.to_json();
+        let mut restored = Computer::restore(Snapshot::from_json(&json).unwrap()).unwrap();
+        assert_eq!(restored.snapshot(), snapshot);
+        assert_eq!(restored.get(5_000_000), 42);
+
+        // Branch the execution from the same state
+        let mut fork = restored.clone();
+        restored.input(1);
+        fork.input(10);
+        assert_eq!(restored.execute(), Ok(RunState::NeedsInput));
+        assert_eq!(fork.execute(), Ok(RunState::NeedsInput));
+        assert_eq!(restored.output, vec![5, 6]);
+        assert_eq!(fork.output, vec![5, 15]);
+        assert_eq!(computer.output, vec![5]);
+
+        // Future versions and corrupted cells are rejected
+        let future = Snapshot {
+            version: SNAPSHOT_VERSION + 1,
+            ..snapshot.clone()
+        };
+        assert_eq!(
+            Computer::restore(future).err(),
+            Some(SnapshotError::UnsupportedVersion {
+                version: SNAPSHOT_VERSION + 1
+            })
+        );
+        let corrupted = Snapshot {
+            big_cells: vec![(3, "12x".to_string())],
+            ..snapshot
+        };
+        assert_eq!(
+            Computer::restore(corrupted).err(),
+            Some(SnapshotError::InvalidBigCell {
+                address: 3,
+                value: "12x".to_string()
+            })
+        );
+    }
+}
@@ -0,0 +1,677 @@
+use super::decompiler::Expr;
+use super::ir::BinOp;
+use super::{Computer, ComputerError, Instruction, RunState, Value};
+use rayon::prelude::*;
+use std::collections::{BTreeMap, VecDeque};
+use std::ops::RangeInclusive;
+use std::sync::Arc;
+
+/// Maximum number of instructions executed by a run, symbolic or concrete
+const MAX_CYCLES: u64 = 1_000_000;
+
+/// Value computed from unknowns: an affine combination of the unknowns,
+/// or an operation on other symbolic values
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Sym {
+    /// `constant + sum of coefficient * unknown`, with the unknowns by index
+    Linear {
+        constant: i64,
+        terms: BTreeMap<usize, i64>,
+    },
+    Binary(BinOp, Arc<Sym>, Arc<Sym>),
+}
+
+impl Sym {
+    pub fn constant(value: i64) -> Self {
+        Sym::Linear {
+            constant: value,
+            terms: BTreeMap::new(),
+        }
+    }
+
+    /// Value of the unknown of the given index
+    pub fn unknown(idx: usize) -> Self {
+        Sym::Linear {
+            constant: 0,
+            terms: vec![(idx, 1)].into_iter().collect(),
+        }
+    }
+
+    pub fn as_constant(&self) -> Option<i64> {
+        match self {
+            Sym::Linear { constant, terms } if terms.is_empty() => Some(*constant),
+            _ => None,
+        }
+    }
+
+    /// Applies an operation, keeping sums and products by constants affine
+    fn binary(op: BinOp, a: Sym, b: Sym) -> Sym {
+        let affine = match (op, &a, &b) {
+            (_, a, b) if a.as_constant().is_some() && b.as_constant().is_some() => op
+                .apply(a.as_constant().unwrap(), b.as_constant().unwrap())
+                .map(Sym::constant),
+            (
+                BinOp::Add,
+                Sym::Linear {
+                    constant: ca,
+                    terms: ta,
+                },
+                Sym::Linear {
+                    constant: cb,
+                    terms: tb,
+                },
+            ) => Sym::add(*ca, ta, *cb, tb),
+            (BinOp::Mul, Sym::Linear { constant, terms }, factor)
+            | (BinOp::Mul, factor, Sym::Linear { constant, terms }) => factor
+                .as_constant()
+                .and_then(|factor| Sym::scale(*constant, terms, factor)),
+            _ => None,
+        };
+        affine.unwrap_or_else(|| Sym::Binary(op, Arc::new(a), Arc::new(b)))
+    }
+
+    /// Sum of two affine values, None on overflow
+    fn add(ca: i64, ta: &BTreeMap<usize, i64>, cb: i64, tb: &BTreeMap<usize, i64>) -> Option<Sym> {
+        let mut terms = ta.clone();
+        for (idx, k) in tb.iter() {
+            let coefficient = terms.entry(*idx).or_insert(0);
+            *coefficient = coefficient.checked_add(*k)?;
+        }
+        terms.retain(|_, k| *k != 0);
+        Some(Sym::Linear {
+            constant: ca.checked_add(cb)?,
+            terms,
+        })
+    }
+
+    /// Product of an affine value by a constant, None on overflow
+    fn scale(constant: i64, terms: &BTreeMap<usize, i64>, factor: i64) -> Option<Sym> {
+        let mut scaled = BTreeMap::new();
+        for (idx, k) in terms.iter() {
+            scaled.insert(*idx, k.checked_mul(factor)?);
+        }
+        scaled.retain(|_, k| *k != 0);
+        Some(Sym::Linear {
+            constant: constant.checked_mul(factor)?,
+            terms: scaled,
+        })
+    }
+
+    /// Evaluates the value for the given values of the unknowns. None on overflow
+    pub fn eval(&self, values: &[i64]) -> Option<i64> {
+        match self {
+            Sym::Linear { constant, terms } => terms.iter().try_fold(*constant, |sum, (idx, k)| {
+                sum.checked_add(k.checked_mul(values[*idx])?)
+            }),
+            Sym::Binary(op, a, b) => op.apply(a.eval(values)?, b.eval(values)?),
+        }
+    }
+
+    /// Number of unknowns up to the last one the value depends on
+    fn unknowns_count(&self) -> usize {
+        match self {
+            Sym::Linear { terms, .. } => terms.keys().last().map_or(0, |idx| idx + 1),
+            Sym::Binary(_, a, b) => a.unknowns_count().max(b.unknowns_count()),
+        }
+    }
+
+    /// Expression of the value, naming the unknowns
+    fn to_expr(&self, names: &[String]) -> Expr {
+        match self {
+            Sym::Linear { constant, terms } => Expr::from_affine((
+                *constant,
+                terms
+                    .iter()
+                    .map(|(idx, k)| (names[*idx].clone(), *k))
+                    .collect(),
+            )),
+            Sym::Binary(op, a, b) => {
+                Expr::Binary(*op, Box::new(a.to_expr(names)), Box::new(b.to_expr(names)))
+            }
+        }
+    }
+}
+
+/// Displays the unknowns as `u0, u1...`
+impl std::fmt::Display for Sym {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let count = self.unknowns_count();
+        let names: Vec<String> = (0..count).map(|idx| format!("u{}", idx)).collect();
+        write!(f, "{}", self.to_expr(&names))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SymbolicError {
+    Computer(ComputerError),
+    /// The instruction at the given address cannot be executed without knowing the unknowns:
+    /// its opcode, an address, a jump or the relative base depend on them
+    DependsOnUnknowns {
+        address: usize,
+    },
+}
+
+impl std::fmt::Display for SymbolicError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            SymbolicError::Computer(e) => write!(f, "{}", e),
+            SymbolicError::DependsOnUnknowns { address } => write!(
+                f,
+                "instruction at address {} depends on the unknowns",
+                address
+            ),
+        }
+    }
+}
+
+impl std::error::Error for SymbolicError {}
+
+impl From<ComputerError> for SymbolicError {
+    fn from(e: ComputerError) -> Self {
+        SymbolicError::Computer(e)
+    }
+}
+
+/// Intcode computer running on symbolic values. The control flow must not depend on the unknowns
+#[derive(Debug, Clone)]
+pub struct SymbolicComputer {
+    memory: Vec<Sym>,
+    pointer: usize,
+    relative_base: i64,
+    inputs: VecDeque<Sym>,
+    pub output: Vec<Sym>,
+    halted: bool,
+}
+
+impl SymbolicComputer {
+    pub fn new(code: &[i64]) -> Self {
+        SymbolicComputer {
+            memory: code.iter().map(|value| Sym::constant(*value)).collect(),
+            pointer: 0,
+            relative_base: 0,
+            inputs: VecDeque::new(),
+            output: vec![],
+            halted: false,
+        }
+    }
+
+    pub fn set(&mut self, addr: usize, value: Sym) {
+        if addr >= self.memory.len() {
+            self.memory.resize(addr + 1, Sym::constant(0));
+        }
+        self.memory[addr] = value;
+    }
+
+    pub fn get(&self, addr: usize) -> Sym {
+        self.memory
+            .get(addr)
+            .cloned()
+            .unwrap_or_else(|| Sym::constant(0))
+    }
+
+    pub fn input(&mut self, value: Sym) {
+        self.inputs.push_back(value);
+    }
+
+    pub fn halted(&self) -> bool {
+        self.halted
+    }
+
+    /// Executes the program until it halts or needs input
+    pub fn execute(&mut self) -> Result<RunState, SymbolicError> {
+        for _ in 0..MAX_CYCLES {
+            if let Some(state) = self.step()? {
+                return Ok(state);
+            }
+        }
+        Ok(RunState::BudgetExhausted)
+    }
+
+    fn depends_on_unknowns(&self) -> SymbolicError {
+        SymbolicError::DependsOnUnknowns {
+            address: self.pointer,
+        }
+    }
+
+    /// Resolves the address targeted by a parameter, when writing
+    fn address(&self, value: &Value) -> Result<usize, SymbolicError> {
+        let target = match value {
+            Value::Immediate(_) => {
+                return Err(ComputerError::WriteToImmediate {
+                    address: self.pointer,
+                }
+                .into())
+            }
+            Value::Position(addr) => *addr as i64,
+            Value::Relative(offset) => {
+                offset
+                    .checked_add(self.relative_base)
+                    .ok_or(ComputerError::Overflow {
+                        address: self.pointer,
+                    })?
+            }
+        };
+        if target < 0 {
+            return Err(ComputerError::NegativeAddress {
+                address: self.pointer,
+                target,
+            }
+            .into());
+        }
+        Ok(target as usize)
+    }
+
+    fn read(&self, value: &Value) -> Result<Sym, SymbolicError> {
+        match value {
+            Value::Immediate(x) => Ok(Sym::constant(*x)),
+            _ => self.address(value).map(|addr| self.get(addr)),
+        }
+    }
+
+    /// Reads a parameter the execution depends on
+    fn read_concrete(&self, value: &Value) -> Result<i64, SymbolicError> {
+        self.read(value)?
+            .as_constant()
+            .ok_or_else(|| self.depends_on_unknowns())
+    }
+
+    fn write(&mut self, value: &Value, sym: Sym) -> Result<(), SymbolicError> {
+        let addr = self.address(value)?;
+        self.set(addr, sym);
+        Ok(())
+    }
+
+    fn step(&mut self) -> Result<Option<RunState>, SymbolicError> {
+        let pointer = self.pointer;
+        if pointer >= self.memory.len() {
+            return Err(ComputerError::PointerOutOfBounds { pointer }.into());
+        }
+        let opcode = self
+            .get(pointer)
+            .as_constant()
+            .ok_or_else(|| self.depends_on_unknowns())?;
+        let mut concrete = true;
+        let instr = Instruction::decode(opcode, pointer, |offset| {
+            let value = self.get(pointer + offset).as_constant();
+            concrete &= value.is_some();
+            value.unwrap_or(0)
+        })?;
+        if !concrete {
+            return Err(self.depends_on_unknowns());
+        }
+
+        let mut next = pointer + instr.args_count() + 1;
+        match instr {
+            Instruction::Add(a, b, c) => self.binary(BinOp::Add, &a, &b, &c)?,
+            Instruction::Mul(a, b, c) => self.binary(BinOp::Mul, &a, &b, &c)?,
+            Instruction::LessThan(a, b, c) => self.binary(BinOp::LessThan, &a, &b, &c)?,
+            Instruction::Equals(a, b, c) => self.binary(BinOp::Equals, &a, &b, &c)?,
+            Instruction::Inp(a) => {
+                self.address(&a)?;
+                match self.inputs.pop_front() {
+                    Some(value) => self.write(&a, value)?,
+                    None => return Ok(Some(RunState::NeedsInput)),
+                }
+            }
+            Instruction::Out(a) => {
+                let value = self.read(&a)?;
+                self.output.push(value);
+            }
+            Instruction::JumpIfTrue(a, b) | Instruction::JumpIfFalse(a, b) => {
+                let jump_if = matches!(instr, Instruction::JumpIfTrue(_, _));
+                if (self.read_concrete(&a)? != 0) == jump_if {
+                    let target = self.read_concrete(&b)?;
+                    if target < 0 {
+                        return Err(ComputerError::NegativeAddress {
+                            address: pointer,
+                            target,
+                        }
+                        .into());
+                    }
+                    next = target as usize;
+                }
+            }
+            Instruction::SetRelativeBase(a) => {
+                self.relative_base = self
+                    .relative_base
+                    .checked_add(self.read_concrete(&a)?)
+                    .ok_or(ComputerError::Overflow { address: pointer })?;
+            }
+            Instruction::Halt => {
+                self.halted = true;
+                return Ok(Some(RunState::Halted));
+            }
+            // Only the standard opcodes are decoded
+            Instruction::Extended { .. } => {
+                return Err(ComputerError::InvalidOpcode {
+                    address: pointer,
+                    opcode,
+                }
+                .into())
+            }
+        }
+        self.pointer = next;
+        Ok(None)
+    }
+
+    fn binary(&mut self, op: BinOp, a: &Value, b: &Value, c: &Value) -> Result<(), SymbolicError> {
+        let value = Sym::binary(op, self.read(a)?, self.read(b)?);
+        self.write(c, value)
+    }
+}
+
+/// Unknown value of a problem, searched within its domain
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Unknown {
+    pub name: String,
+    pub domain: RangeInclusive<i64>,
+}
+
+/// Value a problem solves for
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Target {
+    /// The nth output of the program
+    Output(usize),
+    /// A memory cell, once the program stopped
+    Cell(usize),
+}
+
+/// How a solution was found
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Method {
+    /// Solved as an affine equation
+    Linear,
+    /// Brute force search evaluating the symbolic value of the target
+    Symbolic,
+    /// Brute force search running the program, when the symbolic execution failed
+    Concrete,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Solution {
+    /// Values of the unknowns, in the order they were declared
+    pub values: Vec<i64>,
+    pub method: Method,
+}
+
+/// Program whose memory cells or inputs are partly unknown
+#[derive(Debug, Clone)]
+pub struct Problem {
+    code: Vec<i64>,
+    unknowns: Vec<Unknown>,
+    cells: Vec<(usize, Sym)>,
+    inputs: Vec<Sym>,
+}
+
+impl Problem {
+    pub fn new(code: &[i64]) -> Self {
+        Problem {
+            code: code.to_vec(),
+            unknowns: vec![],
+            cells: vec![],
+            inputs: vec![],
+        }
+    }
+
+    fn unknown(&mut self, name: &str, domain: RangeInclusive<i64>) -> Sym {
+        self.unknowns.push(Unknown {
+            name: name.to_string(),
+            domain,
+        });
+        Sym::unknown(self.unknowns.len() - 1)
+    }
+
+    /// Makes a memory cell unknown
+    pub fn unknown_cell(mut self, addr: usize, name: &str, domain: RangeInclusive<i64>) -> Self {
+        let sym = self.unknown(name, domain);
+        self.cells.push((addr, sym));
+        self
+    }
+
+    /// Adds an unknown value to the input of the program
+    pub fn unknown_input(mut self, name: &str, domain: RangeInclusive<i64>) -> Self {
+        let sym = self.unknown(name, domain);
+        self.inputs.push(sym);
+        self
+    }
+
+    /// Adds a known value to the input of the program
+    pub fn input(mut self, value: i64) -> Self {
+        self.inputs.push(Sym::constant(value));
+        self
+    }
+
+    pub fn unknowns(&self) -> &[Unknown] {
+        &self.unknowns
+    }
+
+    /// Runs the program on the unknowns, until it halts or needs more input
+    pub fn execute(&self) -> Result<SymbolicComputer, SymbolicError> {
+        let (computer, result) = self.execute_partial();
+        result.map(|_| computer)
+    }
+
+    /// Runs the program on the unknowns, keeping the state reached when the execution fails
+    fn execute_partial(&self) -> (SymbolicComputer, Result<RunState, SymbolicError>) {
+        let mut computer = SymbolicComputer::new(&self.code);
+        for (addr, sym) in self.cells.iter() {
+            computer.set(*addr, sym.clone());
+        }
+        for sym in self.inputs.iter() {
+            computer.input(sym.clone());
+        }
+        let result = computer.execute();
+        (computer, result)
+    }
+
+    /// Writes a symbolic value with the names of the unknowns
+    pub fn describe(&self, sym: &Sym) -> String {
+        let names: Vec<String> = self.unknowns.iter().map(|u| u.name.clone()).collect();
+        sym.to_expr(&names).to_string()
+    }
+
+    /// Symbolic value of the target, None if the program does not reach it.
+    /// Outputs produced before the execution depends on the unknowns are still known
+    pub fn target(&self, target: Target) -> Result<Option<Sym>, SymbolicError> {
+        let (computer, result) = self.execute_partial();
+        match (target, result) {
+            (Target::Output(n), _) if n < computer.output.len() => {
+                Ok(Some(computer.output[n].clone()))
+            }
+            (_, Err(e)) => Err(e),
+            (Target::Output(_), Ok(_)) => Ok(None),
+            (Target::Cell(addr), Ok(_)) => Ok(Some(computer.get(addr))),
+        }
+    }
+
+    /// Finds values of the unknowns within their domains, for which the target takes the given value.
+    /// Affine targets are solved as equations, other ones are searched by brute force, in parallel
+    pub fn solve(&self, target: Target, value: i64) -> Option<Solution> {
+        let domains: Vec<RangeInclusive<i64>> =
+            self.unknowns.iter().map(|u| u.domain.clone()).collect();
+        let solution = |values, method| Some(Solution { values, method });
+        match self.target(target) {
+            Ok(Some(Sym::Linear { constant, terms })) => solution(
+                solve_linear(constant, &terms, value, &domains)?,
+                Method::Linear,
+            ),
+            Ok(Some(sym)) => {
+                let (values, _) = search(&domains, |values| {
+                    (sym.eval(values) == Some(value)).then_some(())
+                })?;
+                solution(values, Method::Symbolic)
+            }
+            Ok(None) => None,
+            Err(_) => {
+                let (values, _) = search(&domains, |values| {
+                    (self.run(values, target) == Some(value)).then_some(())
+                })?;
+                solution(values, Method::Concrete)
+            }
+        }
+    }
+
+    /// Runs the program with the given values of the unknowns, returning the value of the target
+    fn run(&self, values: &[i64], target: Target) -> Option<i64> {
+        let mut computer = Computer::new(self.code.clone()).with_cycle_budget(MAX_CYCLES);
+        for (addr, sym) in self.cells.iter() {
+            computer.set(*addr, sym.eval(values)?);
+        }
+        for sym in self.inputs.iter() {
+            computer.input(sym.eval(values)?);
+        }
+        computer.execute().ok()?;
+        match target {
+            Target::Output(n) => computer.get_all_output().nth(n).cloned(),
+            Target::Cell(addr) => Some(computer.get(addr)),
+        }
+    }
+}
+
+/// Solves `constant + sum of coefficient * unknown = value`. The unknown with the largest
+/// domain is computed from the other ones, which are searched
+fn solve_linear(
+    constant: i64,
+    terms: &BTreeMap<usize, i64>,
+    value: i64,
+    domains: &[RangeInclusive<i64>],
+) -> Option<Vec<i64>> {
+    if domains.iter().any(|domain| domain.is_empty()) {
+        return None;
+    }
+    let size = |idx: &usize| domains[*idx].end().saturating_sub(*domains[*idx].start());
+    let pivot = match terms.keys().max_by_key(|idx| size(idx)) {
+        Some(pivot) => *pivot,
+        None if constant == value => return Some(domains.iter().map(|d| *d.start()).collect()),
+        None => return None,
+    };
+    // Unknowns the value does not depend on take the first value of their domain
+    let others: Vec<RangeInclusive<i64>> = (0..domains.len())
+        .map(|idx| match terms.get(&idx) {
+            _ if idx == pivot => 0..=0,
+            Some(_) => domains[idx].clone(),
+            None => *domains[idx].start()..=*domains[idx].start(),
+        })
+        .collect();
+    let coefficient = terms[&pivot];
+    let (mut values, x) = search(&others, |values| {
+        let mut rest = value.checked_sub(constant)?;
+        for (idx, k) in terms.iter().filter(|(idx, _)| **idx != pivot) {
+            rest = rest.checked_sub(k.checked_mul(values[*idx])?)?;
+        }
+        let x = rest.checked_div(coefficient)?;
+        (rest.checked_rem(coefficient)? == 0 && domains[pivot].contains(&x)).then_some(x)
+    })?;
+    values[pivot] = x;
+    Some(values)
+}
+
+/// Finds the first values within the domains, in lexicographic order, accepted by `check`.
+/// The values are checked in parallel
+fn search<T, F>(domains: &[RangeInclusive<i64>], check: F) -> Option<(Vec<i64>, T)>
+where
+    T: Send,
+    F: Fn(&[i64]) -> Option<T> + Sync,
+{
+    let sizes: Vec<u64> = domains
+        .iter()
+        .map(|d| match d.is_empty() {
+            true => 0,
+            false => (*d.end() as i128 - *d.start() as i128 + 1).min(u64::MAX as i128) as u64,
+        })
+        .collect();
+    let total = sizes
+        .iter()
+        .try_fold(1u64, |total, size| total.checked_mul(*size))?;
+    (0..total).into_par_iter().find_map_first(|mut idx| {
+        let mut values = vec![0; domains.len()];
+        for (value, (domain, size)) in values
+            .iter_mut()
+            .zip(domains.iter().zip(sizes.iter()))
+            .rev()
+        {
+            *value = domain.start() + (idx % size) as i64;
+            idx /= size;
+        }
+        check(&values).map(|found| (values, found))
+    })
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::{Method, Problem, Solution, SymbolicComputer, SymbolicError, Target};
+    use crate::intcode_computer::assembler::assemble;
+    use crate::intcode_computer::fixtures::RELATIVE_OVERFLOWS;
+    use crate::intcode_computer::{parse_input, ComputerError};
+
+    #[test]
+    fn symbolic_linear_solver() {
+        // Computes [0] = [13] * 100 + [14] + 7, as the day 2 programs do
+        let code = parse_input("2,13,15,16,1,16,14,17,1,17,18,0,99,0,0,100,0,0,7");
+        let problem = Problem::new(&code)
+            .unknown_cell(13, "noun", 0..=99)
+            .unknown_cell(14, "verb", 0..=99);
+        let result = problem.target(Target::Cell(0)).unwrap().unwrap();
+        assert_eq!(problem.describe(&result), "100 * noun + verb + 7");
+        assert_eq!(
+            problem.solve(Target::Cell(0), 100 * 42 + 17 + 7),
+            Some(Solution {
+                values: vec![42, 17],
+                method: Method::Linear
+            })
+        );
+        assert_eq!(problem.solve(Target::Cell(0), 100 * 99 + 99 + 8), None);
+
+        // Computes [0] = [5] * -1, which never is i64::MIN
+        let problem = Problem::new(&[2, 5, 6, 0, 99, 0, -1]).unknown_cell(5, "x", -10..=10);
+        assert_eq!(problem.solve(Target::Cell(0), i64::MIN), None);
+
+        // Relative base overflowing, then relative address overflowing
+        for code in RELATIVE_OVERFLOWS.iter() {
+            assert_eq!(
+                SymbolicComputer::new(&parse_input(code)).execute(),
+                Err(SymbolicError::Computer(ComputerError::Overflow {
+                    address: 2
+                }))
+            );
+        }
+    }
+
+    #[test]
+    fn symbolic_brute_force() {
+        let code = assemble(
+            "
+                    INP [x]
+                    MUL [x], [x], [square]
+                    OUT [square]
+                    LT [x], #10, [small]
+                    JT [small], #end
+                    OUT #1
+            end:    HLT
+            x:      DB 0
+            square: DB 0
+            small:  DB 0
+            ",
+        )
+        .unwrap();
+        let problem = Problem::new(&code).unknown_input("x", -10..=20);
+        assert_eq!(
+            problem.solve(Target::Output(0), 49),
+            Some(Solution {
+                values: vec![-7],
+                method: Method::Symbolic
+            })
+        );
+        assert_eq!(
+            problem.execute().map(|_| ()),
+            Err(SymbolicError::DependsOnUnknowns { address: 12 })
+        );
+        assert_eq!(
+            problem.solve(Target::Output(1), 1),
+            Some(Solution {
+                values: vec![10],
+                method: Method::Concrete
+            })
+        );
+    }
+}
@@ -0,0 +1,330 @@
+use super::assembler::assemble_with;
+use super::instruction_set::InstructionSet;
+use super::{Computer, ComputerError, Instruction, Memory, RunState};
+use std::str::FromStr;
+
+/// First line of a trace file
+const HEADER: &str = "# intcode trace v1";
+
+/// Record of a single executed instruction
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TraceEntry {
+    /// Address of the instruction
+    pub pointer: usize,
+    /// The executed instruction
+    pub instruction: Instruction,
+    /// Values of the operands, before the execution
+    pub operands: Vec<i64>,
+    /// Memory cells written by the instruction, with their new value
+    pub writes: Vec<(usize, i64)>,
+    /// Inputs consumed by the instruction
+    pub input: Vec<i64>,
+    /// Outputs produced by the instruction
+    pub output: Vec<i64>,
+}
+
+impl TraceEntry {
+    /// Starts recording the given instruction, about to be executed by the computer.
+    /// Also returns the address the instruction writes to, if any
+    pub(super) fn before(computer: &Computer, instr: &Instruction) -> (Self, Option<usize>) {
+        let operands = instr
+            .operands()
+            .iter()
+            .map(|v| computer.read(v).unwrap_or(0))
+            .collect();
+        let destination = instr.destination().and_then(|d| computer.address(d).ok());
+        let entry = TraceEntry {
+            pointer: computer.pointer,
+            instruction: instr.clone(),
+            operands,
+            writes: vec![],
+            input: vec![],
+            output: vec![],
+        };
+        (entry, destination)
+    }
+
+    /// Completes the record once the instruction was executed
+    pub(super) fn after(&mut self, computer: &Computer, destination: Option<usize>) {
+        if let Some(dst) = destination {
+            self.writes.push((dst, computer.get(dst)));
+        }
+        match self.instruction {
+            Instruction::Inp(_) => self.input = self.writes.iter().map(|(_, v)| *v).collect(),
+            Instruction::Out(_) => self.output = self.operands.clone(),
+            Instruction::Extended { .. } => {
+                let effects = &computer.effects;
+                self.writes = effects
+                    .writes
+                    .iter()
+                    .map(|addr| (*addr, computer.get(*addr)))
+                    .collect();
+                self.input = effects.inputs.clone();
+                self.output = effects.outputs.clone();
+            }
+            _ => (),
+        }
+    }
+}
+
+fn join<T: ToString>(values: impl Iterator<Item = T>) -> String {
+    let values: Vec<String> = values.map(|v| v.to_string()).collect();
+    if values.is_empty() {
+        "-".to_string()
+    } else {
+        values.join(",")
+    }
+}
+
+/// Formats the entry as a single line :
+/// `pointer: instruction | operands | writes | input | output`
+impl std::fmt::Display for TraceEntry {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(
+            f,
+            "{}: {} | {} | {} | {} | {}",
+            self.pointer,
+            self.instruction,
+            join(self.operands.iter()),
+            join(self.writes.iter().map(|(a, v)| format!("{}={}", a, v))),
+            join(self.input.iter()),
+            join(self.output.iter()),
+        )
+    }
+}
+
+impl FromStr for TraceEntry {
+    type Err = ();
+
+    fn from_str(line: &str) -> Result<Self, ()> {
+        TraceEntry::parse_with(line, &InstructionSet::standard())
+    }
+}
+
+impl TraceEntry {
+    /// Parses an entry, decoding the opcodes of the given instruction set
+    fn parse_with(line: &str, set: &InstructionSet) -> Result<Self, ()> {
+        let fields: Vec<&str> = line.split('|').map(|f| f.trim()).collect();
+        if fields.len() != 5 {
+            return Err(());
+        }
+        let colon = fields[0].find(':').ok_or(())?;
+        let pointer = fields[0][..colon].parse().map_err(|_| ())?;
+        let cells = assemble_with(&fields[0][colon + 1..], set).map_err(|_| ())?;
+        let instruction = set.decode(&Memory::new(cells), 0).map_err(|_| ())?;
+
+        let list = |field: &str| -> Vec<String> {
+            if field == "-" {
+                vec![]
+            } else {
+                field.split(',').map(|v| v.to_string()).collect()
+            }
+        };
+        let values = |field: &str| -> Result<Vec<i64>, ()> {
+            list(field)
+                .iter()
+                .map(|v| v.parse())
+                .collect::<Result<_, _>>()
+                .map_err(|_| ())
+        };
+        let operands = values(fields[1])?;
+        let writes = list(fields[2])
+            .iter()
+            .map(|w| {
+                let mut parts = w.split('=');
+                let addr = parts.next()?.parse().ok()?;
+                let value = parts.next()?.parse().ok()?;
+                Some((addr, value))
+            })
+            .collect::<Option<_>>()
+            .ok_or(())?;
+        Ok(TraceEntry {
+            pointer,
+            instruction,
+            operands,
+            writes,
+            input: values(fields[3])?,
+            output: values(fields[4])?,
+        })
+    }
+}
+
+/// Error while parsing a trace, with the (1-based) number of the faulty line
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseTraceError {
+    pub line: usize,
+}
+
+impl std::fmt::Display for ParseTraceError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "invalid trace entry at line {}", self.line)
+    }
+}
+
+impl std::error::Error for ParseTraceError {}
+
+/// Instructions executed by a computer, in order
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Trace {
+    entries: Vec<TraceEntry>,
+}
+
+impl Trace {
+    pub fn push(&mut self, entry: TraceEntry) {
+        self.entries.push(entry);
+    }
+
+    pub fn entries(&self) -> &[TraceEntry] {
+        &self.entries
+    }
+
+    /// Inputs consumed during the run, in order
+    pub fn inputs(&self) -> impl Iterator<Item = i64> + '_ {
+        self.entries.iter().flat_map(|e| e.input.iter().cloned())
+    }
+
+    /// Outputs produced during the run, in order
+    pub fn outputs(&self) -> impl Iterator<Item = i64> + '_ {
+        self.entries.iter().flat_map(|e| e.output.iter().cloned())
+    }
+
+    /// Writes the trace to a file
+    pub fn save(&self, path: impl AsRef<std::path::Path>) -> std::io::Result<()> {
+        std::fs::write(path, self.to_string())
+    }
+
+    /// Reads a trace from a file
+    pub fn load(path: impl AsRef<std::path::Path>) -> std::io::Result<Self> {
+        std::fs::read_to_string(path)?
+            .parse()
+            .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))
+    }
+}
+
+impl std::fmt::Display for Trace {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        writeln!(f, "{}", HEADER)?;
+        for entry in self.entries.iter() {
+            writeln!(f, "{}", entry)?;
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for Trace {
+    type Err = ParseTraceError;
+
+    fn from_str(text: &str) -> Result<Self, ParseTraceError> {
+        Trace::parse_with(text, &InstructionSet::standard())
+    }
+}
+
+impl Trace {
+    /// Parses a trace, decoding the opcodes of the given instruction set
+    pub fn parse_with(text: &str, set: &InstructionSet) -> Result<Self, ParseTraceError> {
+        let entries = text
+            .lines()
+            .enumerate()
+            .filter(|(_, line)| !line.trim().is_empty() && !line.starts_with('#'))
+            .map(|(idx, line)| {
+                TraceEntry::parse_with(line, set).map_err(|_| ParseTraceError { line: idx + 1 })
+            })
+            .collect::<Result<_, _>>()?;
+        Ok(Trace { entries })
+    }
+}
+
+/// First difference between a recorded trace and its replay
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Divergence {
+    /// Index of the first entry that differs
+    pub index: usize,
+    /// The entry from the recorded trace
+    pub expected: TraceEntry,
+    /// The entry from the replay, if the replay did not stop before
+    pub actual: Option<TraceEntry>,
+}
+
+impl std::fmt::Display for Divergence {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        writeln!(f, "divergence at entry {}", self.index)?;
+        writeln!(f, "expected: {}", self.expected)?;
+        match &self.actual {
+            Some(actual) => write!(f, "actual:   {}", actual),
+            None => write!(f, "actual:   <stopped>"),
+        }
+    }
+}
+
+/// Re-runs a program, feeding it the inputs recorded in the trace,
+/// and reports the first instruction whose execution differs from the trace
+pub fn replay(code: Vec<i64>, trace: &Trace) -> Result<Option<Divergence>, ComputerError> {
+    replay_with(Computer::new(code), trace)
+}
+
+/// Replays a trace on the given computer, such as one with an instruction set
+pub fn replay_with(computer: Computer, trace: &Trace) -> Result<Option<Divergence>, ComputerError> {
+    let mut computer = computer.record_trace();
+    for input in trace.inputs() {
+        computer.input(input);
+    }
+
+    for (index, expected) in trace.entries().iter().enumerate() {
+        let state = computer.step()?;
+        let actual = match (state, computer.trace()) {
+            (Some(RunState::NeedsInput), _) | (_, None) => None,
+            (_, Some(recorded)) => recorded.entries().last(),
+        };
+        if actual != Some(expected) {
+            return Ok(Some(Divergence {
+                index,
+                expected: expected.clone(),
+                actual: actual.cloned(),
+            }));
+        }
+    }
+    Ok(None)
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::{replay, Trace};
+    use crate::intcode_computer::{parse_input, Computer, RunState};
+
+    #[test]
+    fn trace_record_and_replay() {
+        // Outputs the sum of two inputs, using the relative base
+        let code = parse_input("109,20,203,0,203,1,22201,0,1,2,204,2,99");
+        let mut computer = Computer::new(code.clone()).record_trace();
+        computer.input(3);
+        assert_eq!(computer.execute(), Ok(RunState::NeedsInput));
+        computer.input(4);
+        assert_eq!(computer.execute(), Ok(RunState::Halted));
+        let trace = computer.take_trace().unwrap();
+
+        let lines: Vec<String> = trace.entries().iter().map(|e| e.to_string()).collect();
+        assert_eq!(
+            lines,
+            vec![
+                "0: ARB #20 | 20 | - | - | -",
+                "2: INP [rb+0] | 0 | 20=3 | 3 | -",
+                "4: INP [rb+1] | 0 | 21=4 | 4 | -",
+                "6: ADD [rb+0], [rb+1], [rb+2] | 3,4,0 | 22=7 | - | -",
+                "10: OUT [rb+2] | 7 | - | - | 7",
+                "12: HLT | - | - | - | -",
+            ]
+        );
+        assert_eq!(trace.to_string().parse::<Trace>(), Ok(trace.clone()));
+        assert_eq!(trace.outputs().collect::<Vec<_>>(), vec![7]);
+
+        // The replay does not need the inputs
+        assert_eq!(replay(code.clone(), &trace), Ok(None));
+
+        // Multiplying instead of adding diverges on the fourth instruction
+        let mut patched = code;
+        patched[6] = 22202;
+        let divergence = replay(patched, &trace).unwrap().unwrap();
+        assert_eq!(divergence.index, 3);
+        assert_eq!(divergence.actual.unwrap().writes, vec![(22, 12)]);
+    }
+}
@@ -19,6 +19,6 @@ mod day6;
 mod day7;
 mod day8;
 mod day9;
-mod intcode_computer;
+pub mod intcode_computer;
 
 aoc_lib! { year = 2019 }